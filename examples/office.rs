@@ -12,10 +12,13 @@ use lighting_controller as lc;
 use utility as u;
 
 use bl602_hal as hal;
+#[cfg(feature = "debug-serial")]
 use core::fmt::Write;
 use embedded_time::rate::*;
 use hal::{pac, prelude::*};
-use panic_write as _;
+#[cfg(not(feature = "debug-serial"))]
+use panic_halt as _;
+#[cfg(feature = "debug-serial")]
 use panic_write::PanicHandler;
 
 // How many LEDs on each wall animation:
@@ -30,26 +33,33 @@ pub const CLOSET_STRIP: strip::PhysicalStrip = strip::PhysicalStrip {
     reversed: false,
     color_order: strip::ColorOrder::BRG,
     strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    brightness: 255,
+    inverted: false,
 };
 pub const WINDOW_STRIP: strip::PhysicalStrip = strip::PhysicalStrip {
     led_count: 74,
     reversed: false,
     color_order: strip::ColorOrder::BRG,
     strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    brightness: 255,
+    inverted: false,
 };
 pub const DOOR_STRIP: strip::PhysicalStrip = strip::PhysicalStrip {
     led_count: 59,
     reversed: true,
     color_order: strip::ColorOrder::BRG,
     strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    brightness: 255,
+    inverted: false,
 };
 
 pub const NUM_STRIPS: usize = 3;
 // combined strip group, make sure your pins in main() are in the same order as the strip order here:
 pub const ALL_STRIPS: [strip::PhysicalStrip; NUM_STRIPS] = [CLOSET_STRIP, WINDOW_STRIP, DOOR_STRIP];
 
-// calculate the total number of LEDs from the above values:
+// calculate the total number of LEDs and bytes from the above values:
 pub const NUM_LEDS: usize = crate::get_total_num_leds(&ALL_STRIPS);
+pub const NUM_BYTES: usize = crate::get_total_num_bytes(&ALL_STRIPS);
 
 #[riscv_rt::entry]
 fn main() -> ! {
@@ -66,8 +76,10 @@ fn main() -> ! {
     let (timer_ch0, mut timer_ch1) = u::init_timers(dp.TIMER, &clocks);
 
     // Set up uart output for debug printing. Since this microcontroller has a pin matrix,
-    // we need to set up both the pins and the muxes
-    let serial = u::init_usb_serial(
+    // we need to set up both the pins and the muxes. Without the debug-serial feature, pins 16
+    // and 7 are left free and panics just halt.
+    #[cfg(feature = "debug-serial")]
+    let serial = debug_serial::init_usb_serial(
         dp.UART,
         clocks,
         2_000_000.Bd(),
@@ -78,8 +90,10 @@ fn main() -> ! {
     );
 
     // writes panic messages to serial to see where things went wrong
+    #[cfg(feature = "debug-serial")]
     let mut serial = PanicHandler::new(serial);
 
+    #[cfg(feature = "debug-serial")]
     writeln!(serial, "Debug Serial Initialized...\r").ok();
 
     // The order of pins here needs to match the array of strips passed into LogicalStrip::new()
@@ -89,7 +103,7 @@ fn main() -> ! {
         &mut gpio.pin1.into_pull_down_output(),
     ];
 
-    let mut memory_buffer = [0; NUM_LEDS * 3];
+    let mut memory_buffer = [0; NUM_BYTES];
     let mut color_buffer: [c::Color; NUM_LEDS] = [c::Color::default(); NUM_LEDS];
     let office_strip = strip::LogicalStrip::new(&mut memory_buffer, &mut color_buffer, &ALL_STRIPS);
 
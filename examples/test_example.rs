@@ -37,26 +37,33 @@ pub const CLOSET_STRIP: strip::PhysicalStrip = strip::PhysicalStrip {
     reversed: false,
     color_order: strip::ColorOrder::BRG,
     strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    brightness: 255,
+    inverted: false,
 };
 pub const WINDOW_STRIP: strip::PhysicalStrip = strip::PhysicalStrip {
     led_count: 74,
     reversed: false,
     color_order: strip::ColorOrder::BRG,
     strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    brightness: 255,
+    inverted: false,
 };
 pub const DOOR_STRIP: strip::PhysicalStrip = strip::PhysicalStrip {
     led_count: 59,
     reversed: true,
     color_order: strip::ColorOrder::BRG,
     strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    brightness: 255,
+    inverted: false,
 };
 
 pub const NUM_STRIPS: usize = 3;
 // combined strip group, make sure your pins in main() are in the same order as the strip order here:
 pub const ALL_STRIPS: [strip::PhysicalStrip; NUM_STRIPS] = [CLOSET_STRIP, WINDOW_STRIP, DOOR_STRIP];
 
-// calculate the total number of LEDs from the above values:
+// calculate the total number of LEDs and bytes from the above values:
 pub const NUM_LEDS: usize = crate::get_total_num_leds(&ALL_STRIPS);
+pub const NUM_BYTES: usize = crate::get_total_num_bytes(&ALL_STRIPS);
 
 #[riscv_rt::entry]
 fn main() -> ! {
@@ -90,7 +97,7 @@ fn main() -> ! {
     writeln!(serial, "Debug Serial Initialized...\r").ok();
 
     // set aside memory for a logical strip
-    let mut memory_buffer = [0; NUM_LEDS * 3];
+    let mut memory_buffer = [0; NUM_BYTES];
     let mut color_buffer: [c::Color; NUM_LEDS] = [c::Color::default(); NUM_LEDS];
     let office_strip = strip::LogicalStrip::new(&mut memory_buffer, &mut color_buffer, &ALL_STRIPS);
 
@@ -26,24 +26,32 @@ pub const STRIP_ONE: strip::PhysicalStrip = strip::PhysicalStrip {
     reversed: false,
     color_order: strip::ColorOrder::GRB,
     strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    brightness: 255,
+    inverted: false,
 };
 pub const STRIP_TWO: strip::PhysicalStrip = strip::PhysicalStrip {
     led_count: 4,
     reversed: true,
     color_order: strip::ColorOrder::GRB,
     strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    brightness: 255,
+    inverted: false,
 };
 pub const STRIP_THREE: strip::PhysicalStrip = strip::PhysicalStrip {
     led_count: 4,
     reversed: false,
     color_order: strip::ColorOrder::GRB,
     strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    brightness: 255,
+    inverted: false,
 };
 pub const STRIP_FOUR: strip::PhysicalStrip = strip::PhysicalStrip {
     led_count: 4,
     reversed: true,
     color_order: strip::ColorOrder::GRB,
     strip_timings: strip::StripTimings::WS2812_ADAFRUIT,
+    brightness: 255,
+    inverted: false,
 };
 
 pub const NUM_STRIPS: usize = 4;
@@ -51,8 +59,9 @@ pub const NUM_STRIPS: usize = 4;
 pub const ALL_STRIPS: [strip::PhysicalStrip; NUM_STRIPS] =
     [STRIP_ONE, STRIP_TWO, STRIP_THREE, STRIP_FOUR];
 
-// calculate the total number of LEDs from the above values:
+// calculate the total number of LEDs and bytes from the above values:
 pub const NUM_LEDS: usize = crate::get_total_num_leds(&ALL_STRIPS);
+pub const NUM_BYTES: usize = crate::get_total_num_bytes(&ALL_STRIPS);
 
 #[riscv_rt::entry]
 fn main() -> ! {
@@ -70,7 +79,7 @@ fn main() -> ! {
 
     // Set up uart output for debug printing. Since this microcontroller has a pin matrix,
     // we need to set up both the pins and the muxes
-    let serial = u::init_usb_serial(
+    let serial = debug_serial::init_usb_serial(
         dp.UART,
         clocks,
         2_000_000.Bd(),
@@ -93,7 +102,7 @@ fn main() -> ! {
         &mut gpio.pin4.into_pull_down_output(),
     ];
 
-    let mut memory_buffer = [0; NUM_LEDS * 3];
+    let mut memory_buffer = [0; NUM_BYTES];
     let mut color_buffer: [c::Color; NUM_LEDS] = [c::Color::default(); NUM_LEDS];
     let strip = strip::LogicalStrip::new(&mut memory_buffer, &mut color_buffer, &ALL_STRIPS);
 
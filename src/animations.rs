@@ -1,14 +1,100 @@
-use crate::colors::Color;
+use crate::colors::{scale8, Color, Palette, C_OFF, C_WHITE, P_RAINBOW};
+use crate::leds::ws28xx::LogicalStrip;
+use crate::math::sin8;
 use crate::{background, foreground, trigger};
+use arrayvec::ArrayVec;
 use embedded_time::rate::*;
+use rand::RngCore;
 
 /// Adjust MAX_NUM_* consts depending on RAM requirements:
 pub(crate) const MAX_NUM_ACTIVE_TRIGGERS: usize = 100;
+pub(crate) const MAX_NUM_ACTIVE_METEORS: usize = 8;
+pub(crate) const MAX_SNAKE_LENGTH: usize = 32;
 
 /// This is the maximum offset value for rotating animations. It's basically the supersampled
 /// resolution of the animation over the entire translation_array of leds.
 pub const MAX_OFFSET: u16 = u16::MAX;
 
+/// This is how much of its brightness a twinkling led keeps each frame while it fades out, out of
+/// 255. Higher values make the twinkles last longer.
+pub const TWINKLE_FADE_FACTOR: u8 = 224;
+
+/// This is the usual gap between lit leds for a theater chase, every third led is lit.
+pub const DEFAULT_THEATER_CHASE_SPACING: usize = 3;
+
+/// New sparks for the fire animation are only added to this many leds at the start of the
+/// translation_array.
+pub const FIRE_SPARK_LED_COUNT: usize = 7;
+
+/// This is the chance out of 256 that a new meteor starts each frame of the meteor animation.
+pub const METEOR_SPAWN_CHANCE: u8 = 24;
+
+/// This is how much the fire animation cools down each frame when it is drawn as an Effect.
+pub const DEFAULT_FIRE_COOLING: u8 = 55;
+
+/// This is how likely new sparks are in the fire animation when it is drawn as an Effect.
+pub const DEFAULT_FIRE_SPARKING: u8 = 120;
+
+/// The drawings built into Animation, so one can be picked at runtime by index and stepped
+/// through with `next()`, for example on a button press. See `Animation::render_effect()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Effect {
+    Comet,
+    Larson,
+    TheaterChase,
+    Wipe,
+    Breathe,
+    Bounce,
+    Snake,
+    Meteor,
+    Fire,
+    Twinkle,
+    PaletteScroll,
+    Plasma,
+}
+
+impl Effect {
+    pub const ALL: [Effect; 12] = [
+        Effect::Comet,
+        Effect::Larson,
+        Effect::TheaterChase,
+        Effect::Wipe,
+        Effect::Breathe,
+        Effect::Bounce,
+        Effect::Snake,
+        Effect::Meteor,
+        Effect::Fire,
+        Effect::Twinkle,
+        Effect::PaletteScroll,
+        Effect::Plasma,
+    ];
+
+    pub const fn count() -> usize {
+        Effect::ALL.len()
+    }
+
+    // this is the effect at index, wrapping around past the last one:
+    pub const fn from_index(index: usize) -> Effect {
+        Effect::ALL[index % Effect::count()]
+    }
+
+    // this is the effect after this one, going back to the first one after the last:
+    pub const fn next(self) -> Effect {
+        Effect::from_index(self as usize + 1)
+    }
+}
+
+/// The settings used by `Animation::render_effect()`. Effects ignore the settings they don't use.
+/// The size is the tail length of the comet and larson scanner, the width of the bounce, the
+/// length of the snake and the spread of the palette scroll. The density is used by the twinkle.
+#[derive(Copy, Clone)]
+pub struct EffectParameters<'a> {
+    pub color: Color,
+    pub palette: Palette<'a>,
+    pub size: usize,
+    pub density: u8,
+}
+
 /// Denotes the direction of animations, effects vary depending on animation modes:
 #[derive(Copy, Clone)]
 pub enum Direction {
@@ -44,8 +130,18 @@ pub struct Animation<'a, const N_LED: usize> {
     fg_state: foreground::Foreground<'a>,
     bg_state: background::Background<'a>,
     triggers: trigger::TriggerCollection<'a, MAX_NUM_ACTIVE_TRIGGERS>,
+    heat: [u8; N_LED],
+    bounce_position: usize,
+    bounce_direction: Direction,
+    meteor_brightness: [u8; N_LED],
+    meteor_heads: ArrayVec<usize, MAX_NUM_ACTIVE_METEORS>,
+    snake_body: ArrayVec<usize, MAX_SNAKE_LENGTH>,
+    snake_direction: Direction,
 }
 
+/// This is the per-frame step of an animation. The LightingController calls `update()` once every
+/// frame, then copies the resulting `segment()` onto the leds listed in `translation_array()`.
+/// Animations track their own frame count, so they don't need a timer of their own.
 pub trait Animatable<'a> {
     fn update(&mut self);
     fn set_offset(&mut self, a_type: AnimationType, offset: u16);
@@ -54,6 +150,51 @@ pub trait Animatable<'a> {
     fn translation_array(&self) -> &[usize];
 }
 
+/// A drawing that can be stepped one frame at a time and swapped at runtime, for example by keeping
+/// a `&mut dyn AnimationStep` in the main loop and calling `render()` once every frame. Each step
+/// works out its own phase from the frame number, so it doesn't need a timer of its own.
+pub trait AnimationStep {
+    fn render(&mut self, strip: &mut LogicalStrip, frame: u32);
+}
+
+/// This scrolls one whole rainbow along the logical strip, moving speed 256ths of the way around
+/// the rainbow every frame.
+pub struct RainbowStep {
+    pub speed: u8,
+}
+
+impl AnimationStep for RainbowStep {
+    fn render(&mut self, strip: &mut LogicalStrip, frame: u32) {
+        let led_count = strip.led_count();
+        let hue_offset = frame.wrapping_mul(self.speed as u32) as u8;
+        for index in 0..led_count {
+            let position = ((index * 256) / led_count) as u8;
+            strip.set_color_at_index(index, P_RAINBOW.sample(position.wrapping_add(hue_offset)));
+        }
+    }
+}
+
+/// This fills the whole logical strip with one color that fades up and down like breathing,
+/// taking period frames for each breath. The first frame of each breath is the dimmest.
+pub struct BreatheStep {
+    pub color: Color,
+    pub period: u32,
+}
+
+impl AnimationStep for BreatheStep {
+    fn render(&mut self, strip: &mut LogicalStrip, frame: u32) {
+        let period = self.period.max(1);
+        let phase = ((frame % period) as u64 * 256 / period as u64) as u8;
+        strip.set_strip_to_solid_color(self.color.scale(breath_brightness(phase)));
+    }
+}
+
+// this is the brightness of a breath at phase out of 256, following a sine wave from the dimmest
+// point at 0 to the brightest at 128:
+fn breath_brightness(phase: u8) -> u8 {
+    sin8(phase.wrapping_sub(64))
+}
+
 impl<'a, const N_LED: usize> Animatable<'a> for Animation<'a, N_LED> {
     fn update(&mut self) {
         // Update all three states
@@ -109,6 +250,788 @@ impl<'a, const N_LED: usize> Animation<'a, N_LED> {
         let bg_state = background::Background::new(&parameters.bg, frame_rate);
         let triggers = trigger::TriggerCollection::new(&parameters.trigger, frame_rate);
 
-        Animation { translation_array, segment, fg_state, bg_state, triggers }
+        let heat = [0; N_LED];
+
+        Animation {
+            translation_array,
+            segment,
+            fg_state,
+            bg_state,
+            triggers,
+            heat,
+            bounce_position: 0,
+            bounce_direction: Direction::Positive,
+            meteor_brightness: [0; N_LED],
+            meteor_heads: ArrayVec::new(),
+            snake_body: ArrayVec::new(),
+            snake_direction: Direction::Positive,
+        }
+    }
+
+    // this draws a comet onto the leds of this animation's translation_array, with its head at
+    // position and a tail of tail leds behind it that fade out linearly. Everything else in the
+    // animation is turned off. The tail is clamped at the start of the translation_array instead
+    // of wrapping around, so advancing position every frame makes the comet slide in from the
+    // start. Any part of the comet past the end of the translation_array is not drawn:
+    pub fn comet(&mut self, strip: &mut LogicalStrip, position: usize, tail: usize, color: Color) {
+        for (index, &led) in self.translation_array.iter().enumerate() {
+            let comet_color = match position.checked_sub(index) {
+                Some(distance) if distance <= tail => {
+                    Color::color_lerp(distance as i32, 0, tail as i32 + 1, color, C_OFF)
+                }
+                _ => C_OFF,
+            };
+            strip.set_color_at_index(led, comet_color);
+        }
+    }
+
+    // this draws frame number frame of the effect, working out the position, phase or offset of
+    // each drawing from the frame number, so incrementing frame every frame animates any of them.
+    // The rng is only used by the meteor, fire and twinkle effects:
+    pub fn render_effect(
+        &mut self,
+        strip: &mut LogicalStrip,
+        effect: Effect,
+        frame: u32,
+        parameters: &EffectParameters,
+        rng: &mut impl RngCore,
+    ) {
+        let frame_index = frame as usize;
+        let color = parameters.color;
+        let size = parameters.size.max(1);
+
+        match effect {
+            Effect::Comet => self.comet(strip, frame_index % (N_LED + size), size, color),
+            Effect::Larson => {
+                // sweep from the first led to the last and back again:
+                let period = (2 * N_LED.saturating_sub(1)).max(1);
+                let step = frame_index % period;
+                self.larson(strip, color, step.min(period - step), size);
+            }
+            Effect::TheaterChase => {
+                self.theater_chase(strip, color, DEFAULT_THEATER_CHASE_SPACING, frame_index)
+            }
+            Effect::Wipe => {
+                // wipe the color on and then wipe it back off again:
+                let wipe_length = strip.led_count() + 1;
+                let progress = frame_index % (2 * wipe_length);
+                match progress < wipe_length {
+                    true => self.wipe(strip, color, progress, false),
+                    false => self.wipe(strip, C_OFF, progress - wipe_length, false),
+                }
+            }
+            Effect::Breathe => self.breathe(strip, color, frame as u8),
+            Effect::Bounce => self.bounce(strip, color, size),
+            Effect::Snake => self.snake(strip, color, size, false),
+            Effect::Meteor => self.meteor(strip, color, rng),
+            Effect::Fire => self.fire(strip, DEFAULT_FIRE_COOLING, DEFAULT_FIRE_SPARKING, rng),
+            Effect::Twinkle => self.twinkle(strip, color, parameters.density, rng),
+            Effect::PaletteScroll => {
+                self.palette_scroll(strip, &parameters.palette, frame as u8, size as u8)
+            }
+            Effect::Plasma => self.plasma(strip, &parameters.palette, frame),
+        }
+    }
+
+    // this draws a larson scanner onto the leds of this animation's translation_array, with its
+    // head at position and tail leds on each side of it that fade out linearly, so unlike the
+    // comet the head is always the brightest led in the middle. Everything else in the animation
+    // is turned off. Sweeping position back and forth makes the classic scanner effect:
+    pub fn larson(&mut self, strip: &mut LogicalStrip, color: Color, position: usize, tail: usize) {
+        for (index, &led) in self.translation_array.iter().enumerate() {
+            let larson_color = match index.abs_diff(position) {
+                distance if distance <= tail => {
+                    Color::color_lerp(distance as i32, 0, tail as i32 + 1, color, C_OFF)
+                }
+                _ => C_OFF,
+            };
+            strip.set_color_at_index(led, larson_color);
+        }
+    }
+
+    // this lights every spacing-th led of the whole logical strip with color and turns the rest
+    // off, like the lights on a theater marquee. Leds are lit where (index + offset) % spacing is
+    // 0, so incrementing offset every frame makes the lit leds march along the strip. A spacing of
+    // 0 is treated as 1, lighting every led:
+    pub fn theater_chase(
+        &mut self,
+        strip: &mut LogicalStrip,
+        color: Color,
+        spacing: usize,
+        offset: usize,
+    ) {
+        let spacing = spacing.max(1);
+        let offset = offset % spacing;
+        for index in 0..strip.led_count() {
+            let chase_color = match (index + offset) % spacing == 0 {
+                true => color,
+                false => C_OFF,
+            };
+            strip.set_color_at_index(index, chase_color);
+        }
+    }
+
+    // this draws a block of width leds in color onto this animation's translation_array, turning
+    // everything else off, and then moves the block one led along. The block reverses direction
+    // when it reaches either end, so calling this every frame bounces it back and forth:
+    pub fn bounce(&mut self, strip: &mut LogicalStrip, color: Color, width: usize) {
+        let last_position = N_LED.saturating_sub(width);
+        let position = self.bounce_position.min(last_position);
+        let block = position..position + width;
+
+        for (index, &led) in self.translation_array.iter().enumerate() {
+            let bounce_color = match block.contains(&index) {
+                true => color,
+                false => C_OFF,
+            };
+            strip.set_color_at_index(led, bounce_color);
+        }
+
+        self.bounce_direction = match self.bounce_direction {
+            Direction::Positive if position >= last_position => Direction::Negative,
+            Direction::Negative if position == 0 => Direction::Positive,
+            direction => direction,
+        };
+        self.bounce_position = match self.bounce_direction {
+            Direction::Positive => (position + 1).min(last_position),
+            Direction::Negative => position.saturating_sub(1),
+            Direction::Stopped => position,
+        };
+    }
+
+    // this moves a snake one led along this animation's translation_array and draws it, turning
+    // everything else off. The body follows the exact path the head took over the last length
+    // frames, getting dimmer towards the tail. If wraps is true the head goes back to the start
+    // after the last led, otherwise it turns around at each end and the body follows it back.
+    // The length is limited to MAX_SNAKE_LENGTH:
+    pub fn snake(&mut self, strip: &mut LogicalStrip, color: Color, length: usize, wraps: bool) {
+        let length = length.clamp(1, MAX_SNAKE_LENGTH);
+        let last_position = N_LED.saturating_sub(1);
+
+        let head = match self.snake_body.first() {
+            None => 0,
+            Some(&head) if wraps => (head + 1) % N_LED.max(1),
+            Some(&head) => {
+                self.snake_direction = match self.snake_direction {
+                    Direction::Positive if head >= last_position => Direction::Negative,
+                    Direction::Negative if head == 0 => Direction::Positive,
+                    direction => direction,
+                };
+                match self.snake_direction {
+                    Direction::Positive => (head + 1).min(last_position),
+                    Direction::Negative => head.saturating_sub(1),
+                    Direction::Stopped => head,
+                }
+            }
+        };
+        self.snake_body.truncate(length - 1);
+        self.snake_body.insert(0, head);
+
+        for &led in self.translation_array.iter() {
+            strip.set_color_at_index(led, C_OFF);
+        }
+        // the body is drawn from the tail up, so the head is on top where the body crosses itself:
+        for (segment, &position) in self.snake_body.iter().enumerate().rev() {
+            if let Some(&led) = self.translation_array.get(position) {
+                let segment_color =
+                    Color::color_lerp(segment as i32, 0, length as i32, color, C_OFF);
+                strip.set_color_at_index(led, segment_color);
+            }
+        }
+    }
+
+    // this draws one frame of a meteor shower onto this animation's translation_array. Every frame
+    // the meteor heads all move one led further along, and a new meteor sometimes starts at the
+    // first led. The trails they leave behind fade out at random rates, so some of each trail
+    // lingers while the rest disappears quickly, which makes it shimmer:
+    pub fn meteor(&mut self, strip: &mut LogicalStrip, color: Color, decay_rng: &mut impl RngCore) {
+        // randomly fade every led of the trails, some quickly and some slowly:
+        for brightness in self.meteor_brightness.iter_mut() {
+            let random = decay_rng.next_u32();
+            if random & 1 == 1 {
+                *brightness = scale8(*brightness, (random >> 8) as u8 | 0x80);
+            }
+        }
+
+        // move the heads along by one led, dropping any that fall off the end, then maybe start a
+        // new one at the beginning:
+        self.meteor_heads.iter_mut().for_each(|head| *head += 1);
+        self.meteor_heads.retain(|head| *head < N_LED);
+        if N_LED > 0 && (decay_rng.next_u32() as u8) < METEOR_SPAWN_CHANCE {
+            self.meteor_heads.try_push(0).ok();
+        }
+        for &head in self.meteor_heads.iter() {
+            self.meteor_brightness[head] = u8::MAX;
+        }
+
+        let leds = self
+            .translation_array
+            .iter()
+            .zip(self.meteor_brightness.iter());
+        for (&led, &brightness) in leds {
+            strip.set_color_at_index(led, color.scale(brightness));
+        }
+    }
+
+    // this draws one frame of a flickering fire onto the leds of this animation's
+    // translation_array, with the base of the fire at the start. Each led keeps a heat value
+    // between frames that cools down by a random amount based on cooling, drifts up the fire,
+    // and is occasionally reheated by a random spark near the base based on sparking. Higher
+    // cooling gives shorter flames, and higher sparking gives a more active fire:
+    pub fn fire(
+        &mut self,
+        strip: &mut LogicalStrip,
+        cooling: u8,
+        sparking: u8,
+        rng: &mut impl RngCore,
+    ) {
+        if N_LED == 0 {
+            return;
+        }
+
+        // cool every led down a little:
+        let max_cooling = (cooling as usize * 10 / N_LED) as u32 + 2;
+        for heat in self.heat.iter_mut() {
+            *heat = heat.saturating_sub((rng.next_u32() % max_cooling) as u8);
+        }
+
+        // let the heat from each led drift up and spread out a little:
+        for index in (2..N_LED).rev() {
+            let drifted = self.heat[index - 1] as u16 + 2 * self.heat[index - 2] as u16;
+            self.heat[index] = (drifted / 3) as u8;
+        }
+
+        // randomly ignite new sparks near the bottom:
+        if (rng.next_u32() as u8) < sparking {
+            let index = rng.next_u32() as usize % FIRE_SPARK_LED_COUNT.min(N_LED);
+            let spark = 160 + (rng.next_u32() % 96) as u8;
+            self.heat[index] = self.heat[index].saturating_add(spark);
+        }
+
+        for (&led, &heat) in self.translation_array.iter().zip(self.heat.iter()) {
+            strip.set_color_at_index(led, Color::from_heat(heat));
+        }
+    }
+
+    // this fills the first progress leds of the whole logical strip with color and leaves the
+    // rest as they were, so incrementing progress every frame wipes the color along the strip
+    // one led at a time. If reversed is true, the wipe starts from the last led instead. Any
+    // progress past the end of the strip fills the entire strip:
+    pub fn wipe(
+        &mut self,
+        strip: &mut LogicalStrip,
+        color: Color,
+        progress: usize,
+        reversed: bool,
+    ) {
+        let led_count = strip.led_count();
+        let progress = progress.min(led_count);
+        let (start, end) = match reversed {
+            true => (led_count - progress, led_count),
+            false => (0, progress),
+        };
+        // the range is always inside the strip, so this can't fail:
+        strip.set_range_to_color(start, end, color).ok();
+    }
+
+    // this fills the whole logical strip with color at a brightness that smoothly rises and falls
+    // like breathing. The brightness follows a sine wave, so it eases in and out at the top and
+    // bottom instead of turning around abruptly. A phase of 0 is the dimmest point and 128 is the
+    // brightest, so incrementing phase every frame gives a continuous breath every 256 frames:
+    pub fn breathe(&mut self, strip: &mut LogicalStrip, color: Color, phase: u8) {
+        strip.set_strip_to_solid_color(color.scale(breath_brightness(phase)));
+    }
+
+    // this spreads the palette along the leds of this animation's translation_array, with each
+    // led spread positions further through the palette than the one before it, so a bigger
+    // spread fits more of the palette on the strip. Incrementing offset every frame scrolls the
+    // palette along the strip:
+    pub fn palette_scroll(
+        &mut self,
+        strip: &mut LogicalStrip,
+        palette: &Palette,
+        offset: u8,
+        spread: u8,
+    ) {
+        for (index, &led) in self.translation_array.iter().enumerate() {
+            let position = (index as u8).wrapping_mul(spread).wrapping_add(offset);
+            strip.set_color_at_index(led, palette.sample(position));
+        }
+    }
+
+    // this draws color onto the leds of this animation's translation_array with a brightness
+    // that follows a sine wave along the strip, repeating every wavelength leds. Incrementing
+    // phase every frame scrolls the wave along the strip. A wavelength of 0 has no wave, so every
+    // led is set to the full color:
+    pub fn wave(&mut self, strip: &mut LogicalStrip, color: Color, wavelength: u8, phase: u8) {
+        for (index, &led) in self.translation_array.iter().enumerate() {
+            let brightness = match wavelength {
+                0 => u8::MAX,
+                _ => {
+                    let position = (index % wavelength as usize) * 256 / wavelength as usize;
+                    sin8((position as u8).wrapping_add(phase))
+                }
+            };
+            strip.set_color_at_index(led, color.scale(brightness));
+        }
+    }
+
+    // this draws a flowing plasma onto the leds of this animation's translation_array. Two sine
+    // waves of different lengths move along the strip in opposite directions at different
+    // speeds, and their average picks each led's color from the palette. Incrementing time every
+    // frame makes the plasma flow, and the same time always draws the same frame:
+    pub fn plasma(&mut self, strip: &mut LogicalStrip, palette: &Palette, time: u32) {
+        for (index, &led) in self.translation_array.iter().enumerate() {
+            let index = index as u32;
+            let short_wave = sin8(index.wrapping_mul(16).wrapping_add(time) as u8);
+            let long_wave = sin8(index.wrapping_mul(5).wrapping_sub(time.wrapping_mul(3)) as u8);
+            let position = ((short_wave as u16 + long_wave as u16) / 2) as u8;
+            strip.set_color_at_index(led, palette.sample(position));
+        }
+    }
+
+    // this draws one frame of a twinkling starfield onto the leds of this animation's
+    // translation_array. Every led has a density / 256 chance of being lit to color, and all the
+    // other leds fade towards off a little more, so a density of 0 never lights anything and 255
+    // lights nearly every led. The rng is passed in so any seeded rand RngCore can be used:
+    pub fn twinkle(
+        &mut self,
+        strip: &mut LogicalStrip,
+        color: Color,
+        density: u8,
+        rng: &mut impl RngCore,
+    ) {
+        for &led in self.translation_array.iter() {
+            let twinkle_color = match (rng.next_u32() as u8) < density {
+                true => color,
+                false => strip.get_color_at_index(led).scale(TWINKLE_FADE_FACTOR),
+            };
+            strip.set_color_at_index(led, twinkle_color);
+        }
+    }
+
+    // this draws one full rainbow along the leds of this animation's translation_array, starting
+    // hue_offset of the way around the rainbow, and then turns random leds white as glitter. Every
+    // led has a glitter_chance / 256 chance of sparkling each frame, so incrementing hue_offset
+    // every frame scrolls the rainbow while the glitter flickers on top of it:
+    pub fn rainbow_glitter(
+        &mut self,
+        strip: &mut LogicalStrip,
+        hue_offset: u8,
+        glitter_chance: u8,
+        rng: &mut impl RngCore,
+    ) {
+        for (index, &led) in self.translation_array.iter().enumerate() {
+            let position = ((index * 256) / N_LED) as u8;
+            let rainbow_color = P_RAINBOW.sample(position.wrapping_add(hue_offset));
+            let glitter_color = match (rng.next_u32() as u8) < glitter_chance {
+                true => C_WHITE,
+                false => rainbow_color,
+            };
+            strip.set_color_at_index(led, glitter_color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::{C_BLUE, C_RED, P_HEAT};
+    use crate::default_animations::ANI_ALL_OFF;
+    use crate::leds::ws28xx::PhysicalStrip;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(4).build()];
+    const LONG_STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(8).build()];
+
+    // this is an animation with nothing else going on that covers every led of the strip in
+    // order, for testing the drawings:
+    fn animation<const N_LED: usize>() -> Animation<'static, N_LED> {
+        Animation::new(ANI_ALL_OFF, core::array::from_fn(|index| index), Hertz(60))
+    }
+
+    // this is the red channel of every led, which is enough to compare brightness for drawings
+    // in red or white:
+    fn reds(strip: &LogicalStrip) -> [u8; 8] {
+        core::array::from_fn(|index| strip.get_color_at_index(index).r)
+    }
+
+    #[test]
+    fn breathe_step_rises_and_falls() {
+        let mut bytes = [0; 12];
+        let mut colors = [C_OFF; 4];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+        let mut breathe = BreatheStep { color: C_WHITE, period: 8 };
+
+        let snapshots: [u8; 9] = core::array::from_fn(|frame| {
+            breathe.render(&mut strip, frame as u32);
+            strip.get_color_at_index(3).r
+        });
+        assert!(snapshots[0] <= 1);
+        assert_eq!(snapshots[4], 255);
+        assert_eq!(snapshots[8], snapshots[0]);
+        assert!(snapshots[..5].windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(snapshots[4..].windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    #[test]
+    fn rainbow_step_scrolls_along_the_strip() {
+        let mut bytes = [0; 12];
+        let mut colors = [C_OFF; 4];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+        let mut rainbow = RainbowStep { speed: 64 };
+
+        rainbow.render(&mut strip, 0);
+        let first_frame: [Color; 4] = core::array::from_fn(|index| strip.get_color_at_index(index));
+        assert_eq!(first_frame[0], C_RED);
+
+        // a speed of 64 moves the rainbow along by one of the four leds every frame:
+        rainbow.render(&mut strip, 1);
+        for index in 0..3 {
+            assert_eq!(strip.get_color_at_index(index), first_frame[index + 1]);
+        }
+    }
+
+    #[test]
+    fn comet_head_is_brightest_and_the_tail_fades() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+
+        animation.comet(&mut strip, 5, 3, C_WHITE);
+        let brightness = reds(&strip);
+        assert_eq!(brightness[5], 255);
+        assert!(brightness[2..=5].windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(brightness[..2]
+            .iter()
+            .chain(&brightness[6..])
+            .all(|&red| red == 0));
+
+        // the tail is cut off at the start of the strip instead of wrapping around:
+        animation.comet(&mut strip, 1, 3, C_WHITE);
+        let brightness = reds(&strip);
+        assert!(brightness[0] > 0 && brightness[0] < brightness[1]);
+        assert!(brightness[2..].iter().all(|&red| red == 0));
+    }
+
+    #[test]
+    fn twinkle_is_the_same_for_the_same_seed() {
+        let mut frames = [[C_OFF; 8]; 2];
+        for frame in frames.iter_mut() {
+            let mut bytes = [0; 24];
+            let mut colors = [C_OFF; 8];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+            let mut animation = animation::<8>();
+            let mut rng = SmallRng::seed_from_u64(7);
+            for _ in 0..5 {
+                animation.twinkle(&mut strip, C_WHITE, 64, &mut rng);
+            }
+            *frame = core::array::from_fn(|index| strip.get_color_at_index(index));
+        }
+        assert_eq!(frames[0], frames[1]);
+        assert!(frames[0].iter().any(|color| !color.is_off()));
+
+        // a density of 0 never lights anything:
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut rng = SmallRng::seed_from_u64(7);
+        animation::<8>().twinkle(&mut strip, C_WHITE, 0, &mut rng);
+        assert!(strip.colors().all(|color| color.is_off()));
+    }
+
+    #[test]
+    fn fire_heat_stays_in_range() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+        let mut rng = SmallRng::seed_from_u64(3);
+
+        // the hottest possible fire never overflows, and every led shows its own heat:
+        for _ in 0..500 {
+            animation.fire(&mut strip, 0, 255, &mut rng);
+            for (index, &heat) in animation.heat.iter().enumerate() {
+                assert_eq!(strip.get_color_at_index(index), Color::from_heat(heat));
+            }
+        }
+        assert!(animation.heat.iter().any(|&heat| heat > 0));
+
+        // without any sparks, the fire cools down until it goes out:
+        for _ in 0..500 {
+            animation.fire(&mut strip, 255, 0, &mut rng);
+        }
+        assert!(animation.heat.iter().all(|&heat| heat == 0));
+        assert!(strip.colors().all(|color| color.is_off()));
+    }
+
+    #[test]
+    fn theater_chase_lights_every_third_led() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_WHITE; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+        let is_lit = |strip: &LogicalStrip| -> [bool; 8] {
+            core::array::from_fn(|index| !strip.get_color_at_index(index).is_off())
+        };
+
+        animation.theater_chase(&mut strip, C_RED, DEFAULT_THEATER_CHASE_SPACING, 0);
+        assert_eq!(is_lit(&strip), [true, false, false, true, false, false, true, false]);
+        animation.theater_chase(&mut strip, C_RED, DEFAULT_THEATER_CHASE_SPACING, 1);
+        assert_eq!(is_lit(&strip), [false, false, true, false, false, true, false, false]);
+        // the offset wraps around every spacing leds:
+        animation.theater_chase(&mut strip, C_RED, DEFAULT_THEATER_CHASE_SPACING, 3);
+        assert_eq!(is_lit(&strip), [true, false, false, true, false, false, true, false]);
+    }
+
+    #[test]
+    fn every_effect_renders() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+        let mut rng = SmallRng::seed_from_u64(1);
+        let parameters = EffectParameters { color: C_RED, palette: P_HEAT, size: 3, density: 128 };
+
+        let mut effect = Effect::from_index(0);
+        for index in 0..Effect::count() {
+            assert_eq!(effect, Effect::ALL[index]);
+            for frame in 0..20 {
+                animation.render_effect(&mut strip, effect, frame, &parameters, &mut rng);
+            }
+            effect = effect.next();
+        }
+        assert_eq!(effect, Effect::ALL[0]);
+    }
+
+    #[test]
+    fn breathing_is_smooth_and_symmetric() {
+        let mut bytes = [0; 12];
+        let mut colors = [C_OFF; 4];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+        let mut animation = animation::<4>();
+        let curve: [u8; 256] = core::array::from_fn(|phase| {
+            animation.breathe(&mut strip, C_WHITE, phase as u8);
+            strip.get_color_at_index(0).r
+        });
+
+        assert!(curve[0] <= 1);
+        assert_eq!(curve[128], 255);
+        for phase in 0..256 {
+            let step = curve[phase].abs_diff(curve[(phase + 1) % 256]);
+            assert!(step <= 4, "phase {} jumps by {}", phase, step);
+            assert_eq!(curve[(128 + phase) % 256], curve[(128 + 256 - phase) % 256]);
+        }
+        // it eases in and out instead of turning around abruptly:
+        assert!(curve[126..=130]
+            .windows(2)
+            .all(|pair| pair[0].abs_diff(pair[1]) <= 1));
+        assert!(curve[..=2]
+            .windows(2)
+            .all(|pair| pair[0].abs_diff(pair[1]) <= 1));
+    }
+
+    #[test]
+    fn wipe_fills_from_either_end() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+
+        animation.wipe(&mut strip, C_WHITE, 3, false);
+        assert_eq!(reds(&strip), [255, 255, 255, 0, 0, 0, 0, 0]);
+
+        // the rest of the strip is left as it was:
+        strip.clear();
+        animation.wipe(&mut strip, C_WHITE, 2, true);
+        animation.wipe(&mut strip, C_RED, 1, false);
+        assert_eq!(reds(&strip), [255, 0, 0, 0, 0, 0, 255, 255]);
+
+        animation.wipe(&mut strip, C_WHITE, 100, false);
+        assert!(strip.colors().all(|&color| color == C_WHITE));
+    }
+
+    #[test]
+    fn bounce_reverses_at_both_ends() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+
+        // the first lit led of the block, every frame:
+        let positions: [usize; 15] = core::array::from_fn(|_| {
+            animation.bounce(&mut strip, C_WHITE, 2);
+            let brightness = reds(&strip);
+            assert_eq!(brightness.iter().filter(|&&red| red == 255).count(), 2);
+            brightness.iter().position(|&red| red == 255).unwrap()
+        });
+        assert_eq!(positions, [0, 1, 2, 3, 4, 5, 6, 5, 4, 3, 2, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn meteor_heads_appear_and_their_trails_fade() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+        let mut rng = SmallRng::seed_from_u64(5);
+
+        let (mut head_count, mut trail_count) = (0, 0);
+        for _ in 0..200 {
+            let before = animation.meteor_brightness;
+            animation.meteor(&mut strip, C_WHITE, &mut rng);
+            head_count += animation.meteor_heads.len();
+
+            // every led other than the heads only ever gets dimmer:
+            for (index, &brightness) in animation.meteor_brightness.iter().enumerate() {
+                match animation.meteor_heads.contains(&index) {
+                    true => assert_eq!(brightness, 255),
+                    false => {
+                        assert!(brightness <= before[index]);
+                        trail_count += (brightness > 0) as usize;
+                    }
+                }
+                assert_eq!(strip.get_color_at_index(index), C_WHITE.scale(brightness));
+            }
+        }
+        assert!(head_count > 0 && trail_count > 0);
+    }
+
+    #[test]
+    fn larson_scanner_is_symmetric_around_the_head() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+
+        animation.larson(&mut strip, C_WHITE, 4, 3);
+        let brightness = reds(&strip);
+        assert_eq!(brightness[4], 255);
+        for distance in 1..=3 {
+            assert_eq!(brightness[4 - distance], brightness[4 + distance]);
+            assert!(brightness[4 + distance] < brightness[4 + distance - 1]);
+            assert!(brightness[4 + distance] > 0);
+        }
+        assert_eq!(brightness[0], 0);
+    }
+
+    #[test]
+    fn plasma_draws_the_same_frame_for_the_same_time() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+        let anchors = [C_OFF, C_WHITE];
+        let grays = Palette::new(&anchors, false);
+
+        animation.plasma(&mut strip, &grays, 40);
+        let frame = reds(&strip);
+        animation.plasma(&mut strip, &grays, 41);
+        assert_ne!(reds(&strip), frame);
+        animation.plasma(&mut strip, &grays, 40);
+        assert_eq!(reds(&strip), frame);
+        // the first led is the average of sin8(40) = 234 and sin8(136) = 103:
+        assert_eq!(frame, [168, 170, 163, 147, 123, 94, 64, 37]);
+    }
+
+    #[test]
+    fn palette_scroll_repeats_along_the_strip() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+        let anchors = [C_RED, C_BLUE];
+        let palette = Palette::new(&anchors, true);
+        let frame = |strip: &LogicalStrip| -> [Color; 8] {
+            core::array::from_fn(|index| strip.get_color_at_index(index))
+        };
+
+        // a spread of 128 fits the palette on every two leds:
+        animation.palette_scroll(&mut strip, &palette, 0, 128);
+        let (red, blue) = (C_RED, C_BLUE);
+        assert_eq!(frame(&strip), [red, blue, red, blue, red, blue, red, blue]);
+
+        // a spread of 64 repeats every four leds:
+        animation.palette_scroll(&mut strip, &palette, 0, 64);
+        let colors = frame(&strip);
+        assert_eq!(colors[..4], colors[4..]);
+        assert_eq!((colors[0], colors[2]), (red, blue));
+
+        // moving the offset by one led's worth scrolls the pattern one led along:
+        animation.palette_scroll(&mut strip, &palette, 64, 64);
+        assert_eq!(frame(&strip)[..7], colors[1..]);
+    }
+
+    #[test]
+    fn snake_body_trails_the_head() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let (mut animation, mut wrapping) = (animation::<8>(), animation::<8>());
+
+        for _ in 0..4 {
+            animation.snake(&mut strip, C_WHITE, 3, false);
+        }
+        assert_eq!(animation.snake_body.as_slice(), [3, 2, 1]);
+        let brightness = reds(&strip);
+        assert_eq!(brightness[3], 255);
+        assert!(brightness[3] > brightness[2] && brightness[2] > brightness[1]);
+        assert!(brightness[1] > 0 && brightness[0] == 0 && brightness[4] == 0);
+
+        // without wrapping, the head turns around at the end and the body follows it back:
+        for _ in 0..5 {
+            animation.snake(&mut strip, C_WHITE, 3, false);
+        }
+        assert_eq!(animation.snake_body.as_slice(), [6, 7, 6]);
+        assert_eq!(reds(&strip)[6], 255);
+
+        // with wrapping, the head goes back to the start instead:
+        for _ in 0..9 {
+            wrapping.snake(&mut strip, C_WHITE, 3, true);
+        }
+        assert_eq!(wrapping.snake_body.as_slice(), [0, 7, 6]);
+    }
+
+    #[test]
+    fn rainbow_glitter_sparkles_at_the_expected_rate() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+        let mut rng = SmallRng::seed_from_u64(9);
+
+        // a chance of 32 out of 256 should sparkle about 1000 of the 8000 leds drawn:
+        let mut glitter_count = 0;
+        for hue_offset in 0..1000 {
+            animation.rainbow_glitter(&mut strip, hue_offset as u8, 32, &mut rng);
+            glitter_count += strip.colors().filter(|&&color| color == C_WHITE).count();
+        }
+        assert!((800..1200).contains(&glitter_count), "{} leds sparkled", glitter_count);
+
+        // without any glitter it is just the rainbow:
+        animation.rainbow_glitter(&mut strip, 0, 0, &mut rng);
+        assert_eq!(strip.get_color_at_index(0), C_RED);
+        assert!(strip.colors().all(|&color| color != C_WHITE));
+    }
+
+    #[test]
+    fn wave_brightness_follows_the_sine_table() {
+        let mut bytes = [0; 24];
+        let mut colors = [C_OFF; 8];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &LONG_STRIPS);
+        let mut animation = animation::<8>();
+
+        // a wavelength of 4 leds is a quarter turn of the sine per led:
+        animation.wave(&mut strip, C_WHITE, 4, 16);
+        let brightness = reds(&strip);
+        for (index, &red) in brightness.iter().enumerate() {
+            let theta = ((index % 4) * 64 + 16) as u8;
+            assert_eq!(red, scale8(255, sin8(theta)), "led {}", index);
+        }
+        assert_eq!(brightness[..4], brightness[4..]);
+
+        animation.wave(&mut strip, C_WHITE, 0, 16);
+        assert!(strip.colors().all(|&color| color == C_WHITE));
     }
 }
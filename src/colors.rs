@@ -1,17 +1,107 @@
+use core::ops::{Add, Mul, Sub};
+use rand::RngCore;
+
+/// This is the default gamma correction setting for new LogicalStrips. It can be changed per strip
+/// at runtime using `LogicalStrip::set_gamma_correction()`.
 pub const IS_GAMMA_CORRECTION_ENABLED: bool = true;
 
+// this scales value by factor / 255 using integer math. A factor of 255 returns the same value,
+// and a factor of 0 returns 0:
+pub fn scale8(value: u8, factor: u8) -> u8 {
+    ((value as u16 * (factor as u16 + 1)) >> 8) as u8
+}
+
+/// The ways two colors can be layered on top of each other:
+#[derive(Copy, Clone, Debug)]
+pub enum BlendMode {
+    /// Adds the channels together, clamping at 255.
+    Add,
+    /// Keeps the brightest value of each channel.
+    Max,
+}
+
+/// The easing curves a channel can follow from its start value to its end value:
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Curve {
+    /// Moves at the same speed the whole way.
+    Linear,
+    /// Starts slowly and speeds up towards the end.
+    EaseIn,
+    /// Starts quickly and slows down towards the end.
+    EaseOut,
+}
+
+impl Curve {
+    // this maps t from 0 to 255 onto the curve, which also goes from 0 to 255:
+    pub fn apply(&self, t: u8) -> u8 {
+        match self {
+            Curve::Linear => t,
+            Curve::EaseIn => scale8(t, t),
+            Curve::EaseOut => 255 - scale8(255 - t, 255 - t),
+        }
+    }
+}
+
+/// The `w` channel is only sent to RGBW strips such as the SK6812, and is ignored by RGB strips.
 #[allow(dead_code)]
-#[derive(Default, Copy, Clone, Debug)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     pub r: u8,
     pub g: u8,
     pub b: u8,
+    pub w: u8,
 }
 
 impl Color {
+    pub const OFF: Color = C_OFF;
+    pub const WHITE: Color = C_WHITE;
+
     // new color object takes rgb color values:
-    pub fn new(r: u8, g: u8, b: u8) -> Self {
-        Color { r, g, b }
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, w: 0 }
+    }
+
+    // new color object with a value for the white channel of RGBW strips:
+    pub const fn new_rgbw(r: u8, g: u8, b: u8, w: u8) -> Self {
+        Color { r, g, b, w }
+    }
+
+    // new color object from a 0xRRGGBB value, useful for defining colors inline:
+    pub const fn from_u32(rgb: u32) -> Self {
+        Color { r: (rgb >> 16) as u8, g: (rgb >> 8) as u8, b: rgb as u8, w: 0 }
+    }
+
+    // this looks up one of the NAMED_COLORS by name, ignoring case. Returns None if there is no
+    // color with that name:
+    pub fn from_name(name: &str) -> Option<Self> {
+        NAMED_COLORS
+            .iter()
+            .find(|(color_name, _)| color_name.eq_ignore_ascii_case(name))
+            .map(|&(_, color)| color)
+    }
+
+    // new color object from a "#RRGGBB" or "RRGGBB" hex string in upper or lower case. Returns
+    // None if the string is not exactly 6 hex digits after the optional '#':
+    pub fn from_hex_str(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        u32::from_str_radix(hex, 16).ok().map(Color::from_u32)
+    }
+
+    // new color object with random rgb values from the rng:
+    pub fn random(rng: &mut impl RngCore) -> Self {
+        let [r, g, b, _] = rng.next_u32().to_le_bytes();
+        Color::new(r, g, b)
+    }
+
+    // new color object with a random hue at full saturation and brightness, so it is always a
+    // vivid color rather than a washed out or grayish one:
+    pub fn random_hue(rng: &mut impl RngCore) -> Self {
+        Hsv::new((rng.next_u32() % 360) as u16, 255, 255).to_color()
     }
 
     // change RGB color values for mutable color
@@ -21,14 +111,17 @@ impl Color {
         self.b = b;
     }
 
-    // change RGB color values for mutable color
+    // change RGB and white color values for mutable color
     pub fn set_color(&mut self, color: Color) {
         self.set_rgb(color.r, color.g, color.b);
+        self.w = color.w;
     }
 
     // t=This maps a color to a fractional mid-color based on the position of the factor
-    // between the in_min and in_max values. It will automatically truncate any values
-    // below 0 or larger than 255 when it is cast back to a u8 at the end of the calculation.
+    // between the in_min and in_max values. A factor outside of that range extrapolates past the
+    // end colors, and any channel values below 0 or larger than 255 wrap around when they are
+    // cast back to a u8 at the end of the calculation. Use color_lerp_clamped() if the factor
+    // might be out of range.
     pub fn color_lerp(
         factor: i32,
         in_min: i32,
@@ -46,30 +139,411 @@ impl Color {
         mid_color.r = lerp(start_color.r, end_color.r);
         mid_color.g = lerp(start_color.g, end_color.g);
         mid_color.b = lerp(start_color.b, end_color.b);
+        mid_color.w = lerp(start_color.w, end_color.w);
+
+        mid_color
+    }
+
+    // this works the same as color_lerp(), but a factor outside of the in_min to in_max range is
+    // clamped to it, so it returns exactly the start or end color instead of extrapolating:
+    pub fn color_lerp_clamped(
+        factor: i32,
+        in_min: i32,
+        in_max: i32,
+        start_color: Color,
+        end_color: Color,
+    ) -> Color {
+        let factor = factor.clamp(in_min.min(in_max), in_min.max(in_max));
+        Color::color_lerp(factor, in_min, in_max, start_color, end_color)
+    }
+
+    // this moves each channel from the start color to the end color along its own curve, in the
+    // order r, g, b, w, so channels can fade at different rates in the same transition. t goes
+    // from 0 for the start color to 255 for the end color:
+    pub fn lerp_channels(start_color: Color, end_color: Color, t: u8, curves: [Curve; 4]) -> Color {
+        let lerp = |start: u8, end: u8, curve: Curve| {
+            let (start, end) = (start as i32, end as i32);
+            ((end - start) * curve.apply(t) as i32 / 255 + start) as u8
+        };
+        Color {
+            r: lerp(start_color.r, end_color.r, curves[0]),
+            g: lerp(start_color.g, end_color.g, curves[1]),
+            b: lerp(start_color.b, end_color.b, curves[2]),
+            w: lerp(start_color.w, end_color.w, curves[3]),
+        }
+    }
 
+    // this works the same as color_lerp(), but interpolates in the OKLab color space instead of
+    // RGB. OKLab is perceptually even, so the mid colors don't dip darker or go muddy gray the way
+    // they do in RGB, at the cost of a lot more math. The white channel is interpolated linearly:
+    pub fn color_lerp_oklab(
+        factor: i32,
+        in_min: i32,
+        in_max: i32,
+        start_color: Color,
+        end_color: Color,
+    ) -> Color {
+        let lerp = |start: i64, end: i64| {
+            let (factor, in_min, in_max) = (factor as i64, in_min as i64, in_max as i64);
+            (factor - in_min) * (end - start) / (in_max - in_min) + start
+        };
+        let start = Oklab::from_color(start_color);
+        let end = Oklab::from_color(end_color);
+        let mid =
+            Oklab { l: lerp(start.l, end.l), a: lerp(start.a, end.a), b: lerp(start.b, end.b) };
+
+        let mut mid_color = mid.to_color();
+        mid_color.w = Color::color_lerp(factor, in_min, in_max, start_color, end_color).w;
         mid_color
     }
+
+    // this maps each channel through the GAMMA8 table so the perceived brightness of the LEDs
+    // follows the color values more closely. Off and full-on stay exactly the same.
+    pub fn gamma_correct(&self) -> Color {
+        Color {
+            r: GAMMA8[self.r as usize],
+            g: GAMMA8[self.g as usize],
+            b: GAMMA8[self.b as usize],
+            w: GAMMA8[self.w as usize],
+        }
+    }
+
+    // this scales every channel by factor / 255 using integer math. A factor of 255 returns the
+    // same color, and a factor of 0 turns it off:
+    pub fn scale(&self, factor: u8) -> Color {
+        let scale = |channel: u8| scale8(channel, factor);
+        Color { r: scale(self.r), g: scale(self.g), b: scale(self.b), w: scale(self.w) }
+    }
+
+    // this scales each of the r, g, and b channels by the matching channel of correction out of
+    // 255, to compensate for the color cast of the leds. The white channel is left alone:
+    pub fn correct(&self, correction: Color) -> Color {
+        Color {
+            r: scale8(self.r, correction.r),
+            g: scale8(self.g, correction.g),
+            b: scale8(self.b, correction.b),
+            w: self.w,
+        }
+    }
+
+    // this works like correct() followed by scale(), but keeps the part of each channel that is
+    // lost by rounding down in error, and adds it back on the next time it is called. Calling it
+    // every frame with the same error makes the channels average out to their exact value:
+    pub fn scale_dithered(&self, correction: Color, factor: u8, error: &mut [u8; 4]) -> Color {
+        let factors = [
+            scale8(correction.r, factor),
+            scale8(correction.g, factor),
+            scale8(correction.b, factor),
+            factor,
+        ];
+        let mut channels = [self.r, self.g, self.b, self.w];
+        for ((channel, factor), error) in channels.iter_mut().zip(factors).zip(error.iter_mut()) {
+            let scaled = *channel as u16 * (factor as u16 + 1) + *error as u16;
+            *channel = (scaled >> 8) as u8;
+            *error = scaled as u8;
+        }
+        let [r, g, b, w] = channels;
+        Color { r, g, b, w }
+    }
+
+    // this moves the part of the color that all of r, g and b share onto the white channel, so
+    // (200, 200, 200, 0) becomes (0, 0, 0, 200). The white channel clamps at 255:
+    pub fn extract_white(&self) -> Color {
+        let white = self.r.min(self.g).min(self.b);
+        Color {
+            r: self.r - white,
+            g: self.g - white,
+            b: self.b - white,
+            w: self.w.saturating_add(white),
+        }
+    }
+
+    // this moves every channel at most max_delta closer to the target color, stopping exactly on
+    // the target instead of overshooting it:
+    pub fn step_toward(&self, target: &Color, max_delta: u8) -> Color {
+        let step = |channel: u8, target: u8| match channel < target {
+            true => channel.saturating_add(max_delta).min(target),
+            false => channel.saturating_sub(max_delta).max(target),
+        };
+        Color {
+            r: step(self.r, target.r),
+            g: step(self.g, target.g),
+            b: step(self.b, target.b),
+            w: step(self.w, target.w),
+        }
+    }
+
+    // this subtracts amount from every channel, stopping at 0 instead of wrapping around:
+    pub fn dim(&self, amount: u8) -> Color {
+        Color {
+            r: self.r.saturating_sub(amount),
+            g: self.g.saturating_sub(amount),
+            b: self.b.saturating_sub(amount),
+            w: self.w.saturating_sub(amount),
+        }
+    }
+
+    // this adds the channels of both colors together, clamping them at 255 instead of wrapping:
+    pub fn blend_add(&self, other: &Color) -> Color {
+        Color {
+            r: self.r.saturating_add(other.r),
+            g: self.g.saturating_add(other.g),
+            b: self.b.saturating_add(other.b),
+            w: self.w.saturating_add(other.w),
+        }
+    }
+
+    // this keeps the brighter value of each channel from the two colors:
+    pub fn blend_max(&self, other: &Color) -> Color {
+        Color {
+            r: self.r.max(other.r),
+            g: self.g.max(other.g),
+            b: self.b.max(other.b),
+            w: self.w.max(other.w),
+        }
+    }
+
+    // this combines the two colors using the given blend mode:
+    pub fn blend(&self, other: &Color, mode: BlendMode) -> Color {
+        match mode {
+            BlendMode::Add => self.blend_add(other),
+            BlendMode::Max => self.blend_max(other),
+        }
+    }
+
+    // this draws the overlay color on top of this color with the given alpha, so an alpha of 0
+    // keeps this color and 255 replaces it with the overlay:
+    pub fn composite(&self, overlay: &Color, alpha: u8) -> Color {
+        let mix = |base: u8, over: u8| {
+            let (base, over, alpha) = (base as u16, over as u16, alpha as u16);
+            ((base * (255 - alpha) + over * alpha + 127) / 255) as u8
+        };
+        Color {
+            r: mix(self.r, overlay.r),
+            g: mix(self.g, overlay.g),
+            b: mix(self.b, overlay.b),
+            w: mix(self.w, overlay.w),
+        }
+    }
+
+    // this maps a heat value onto a black body palette, going from black through red and orange
+    // to white as the heat goes from 0 to 255:
+    pub fn from_heat(heat: u8) -> Color {
+        // scale the heat down to 0..191 so it splits evenly into three 64 step ramps:
+        let heat = ((heat as u16 * 192) >> 8) as u8;
+        let ramp = (heat & 0x3F) << 2;
+        match heat {
+            0x80.. => Color::new(255, 255, ramp),
+            0x40.. => Color::new(255, ramp, 0),
+            _ => Color::new(ramp, 0, 0),
+        }
+    }
+
+    // this moves the hue of the color around the color wheel by degrees, keeping its saturation
+    // and value. Grays have no hue, so they stay the same, and so does the white channel:
+    pub fn rotate_hue(&self, degrees: u16) -> Color {
+        let mut hsv = self.to_hsv();
+        if hsv.s == 0 {
+            return *self;
+        }
+        hsv.h = ((hsv.h as u32 + degrees as u32) % 360) as u16;
+        let mut rotated = hsv.to_color();
+        rotated.w = self.w;
+        rotated
+    }
+
+    // this moves the hue around the color wheel by hue_shift degrees in either direction, and
+    // scales the saturation and value by sat_scale / 255 and val_scale / 255. A sat_scale of 0
+    // turns the color into a gray. The white channel is left alone:
+    pub fn adjust_hsv(&self, hue_shift: i16, sat_scale: u8, val_scale: u8) -> Color {
+        let hsv = self.to_hsv();
+        let hue = (hsv.h as i32 + hue_shift as i32).rem_euclid(360) as u16;
+        let mut adjusted =
+            Hsv::new(hue, scale8(hsv.s, sat_scale), scale8(hsv.v, val_scale)).to_color();
+        adjusted.w = self.w;
+        adjusted
+    }
+
+    // this is true if every channel, including white, is 0:
+    pub fn is_off(&self) -> bool {
+        *self == C_OFF
+    }
+
+    // this is true if every channel is within tolerance of the same channel of other, which is
+    // useful when rounding in gamma correction or scaling can be off by one:
+    pub fn is_near(&self, other: &Color, tolerance: u8) -> bool {
+        self.r.abs_diff(other.r) <= tolerance
+            && self.g.abs_diff(other.g) <= tolerance
+            && self.b.abs_diff(other.b) <= tolerance
+            && self.w.abs_diff(other.w) <= tolerance
+    }
+
+    // this is how bright the color looks, using the usual 0.299 r + 0.587 g + 0.114 b weighting
+    // as 77, 150 and 29 out of 256. The white channel is ignored:
+    pub fn luminance(&self) -> u8 {
+        ((self.r as u16 * 77 + self.g as u16 * 150 + self.b as u16 * 29) >> 8) as u8
+    }
+
+    // this sets r, g and b to the luminance of the color, turning it into a gray that looks just
+    // as bright. The white channel is left alone:
+    pub fn to_grayscale(&self) -> Color {
+        let luminance = self.luminance();
+        Color { r: luminance, g: luminance, b: luminance, w: self.w }
+    }
+
+    // this converts an RGB color into its HSV equivalent. Hue is returned in the 0..360 range.
+    // The white channel is not part of HSV, so it is ignored:
+    pub fn to_hsv(&self) -> Hsv {
+        let (r, g, b) = (self.r as i32, self.g as i32, self.b as i32);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        if delta == 0 {
+            // grays have no hue or saturation, only a value:
+            return Hsv { h: 0, s: 0, v: max as u8 };
+        }
+
+        let s = (delta * 255 + max / 2) / max;
+
+        // each of the three cases covers two sextants of the color wheel:
+        let h = if max == r {
+            60 * (g - b) / delta
+        } else if max == g {
+            120 + 60 * (b - r) / delta
+        } else {
+            240 + 60 * (r - g) / delta
+        };
+
+        Hsv { h: h.rem_euclid(360) as u16, s: s as u8, v: max as u8 }
+    }
+}
+
+// the operators are shorthand for the named methods, so a + b is a.blend_add(&b) and c * 128 is
+// c.scale(128). Subtraction clamps each channel at 0 the same way addition clamps at 255:
+impl Add for Color {
+    type Output = Color;
+
+    fn add(self, other: Color) -> Color {
+        self.blend_add(&other)
+    }
+}
+
+impl Sub for Color {
+    type Output = Color;
+
+    fn sub(self, other: Color) -> Color {
+        Color {
+            r: self.r.saturating_sub(other.r),
+            g: self.g.saturating_sub(other.g),
+            b: self.b.saturating_sub(other.b),
+            w: self.w.saturating_sub(other.w),
+        }
+    }
+}
+
+impl Mul<u8> for Color {
+    type Output = Color;
+
+    fn mul(self, factor: u8) -> Color {
+        self.scale(factor)
+    }
+}
+
+/// A color in HSV space. Hue is in degrees and wraps at 360, saturation and value are 0..255.
+/// This is useful for rainbow effects, where stepping through the hue is much simpler than
+/// doing the math in RGB space.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct Hsv {
+    pub h: u16,
+    pub s: u8,
+    pub v: u8,
+}
+
+impl Hsv {
+    // new hsv object takes a hue in degrees, and saturation and value from 0..255:
+    pub fn new(h: u16, s: u8, v: u8) -> Self {
+        Hsv { h, s, v }
+    }
+
+    // this converts to RGB using the standard sextant algorithm with integer math only. The
+    // fractional position inside each 60 degree sextant is scaled to 0..255 so that the rising
+    // and falling channels meet exactly at the sextant boundaries with no gaps.
+    pub fn to_color(&self) -> Color {
+        let hue = (self.h % 360) as u32;
+        let (s, v) = (self.s as u32, self.v as u32);
+
+        let sextant = hue / 60;
+        let fraction = (hue % 60) * 255 / 60;
+
+        let scale = |x: u32| ((v * (255 - x) + 127) / 255) as u8;
+        let p = scale(s);
+        let q = scale(s * fraction / 255);
+        let t = scale(s * (255 - fraction) / 255);
+        let v = v as u8;
+
+        match sextant {
+            0 => Color::new(v, t, p),
+            1 => Color::new(q, v, p),
+            2 => Color::new(p, v, t),
+            3 => Color::new(p, q, v),
+            4 => Color::new(t, p, v),
+            _ => Color::new(v, p, q),
+        }
+    }
 }
 
 // Generic colors:
-pub const C_RED: Color = Color { r: 255, g: 0, b: 0 };
-pub const C_ORANGE: Color = Color { r: 255, g: 127, b: 0 };
-pub const C_YELLOW: Color = Color { r: 255, g: 255, b: 0 };
-pub const C_YELLOW_GREEN: Color = Color { r: 127, g: 255, b: 0 };
-pub const C_GREEN: Color = Color { r: 0, g: 255, b: 0 };
-pub const C_GREEN_BLUE: Color = Color { r: 0, g: 255, b: 127 };
-pub const C_SKY_BLUE: Color = Color { r: 0, g: 255, b: 255 };
-pub const C_DEEP_BLUE: Color = Color { r: 0, g: 127, b: 255 };
-pub const C_BLUE: Color = Color { r: 0, g: 0, b: 255 };
-pub const C_BLUE_PURPLE: Color = Color { r: 127, g: 0, b: 255 };
-pub const C_PURPLE: Color = Color { r: 255, g: 0, b: 255 };
-pub const C_DARK_PURPLE: Color = Color { r: 255, g: 0, b: 127 };
-pub const C_WHITE: Color = Color { r: 255, g: 255, b: 255 };
-pub const C_OFF: Color = Color { r: 0, g: 0, b: 0 };
-pub const C_T_3000K: Color = Color { r: 255, g: 180, b: 107 };
-pub const C_T_3500K: Color = Color { r: 255, g: 196, b: 137 };
-pub const C_T_4000K: Color = Color { r: 255, g: 209, b: 163 };
-pub const C_T_5000K: Color = Color { r: 255, g: 228, b: 206 };
+pub const C_RED: Color = Color { r: 255, g: 0, b: 0, w: 0 };
+pub const C_ORANGE: Color = Color { r: 255, g: 127, b: 0, w: 0 };
+pub const C_YELLOW: Color = Color { r: 255, g: 255, b: 0, w: 0 };
+pub const C_YELLOW_GREEN: Color = Color { r: 127, g: 255, b: 0, w: 0 };
+pub const C_GREEN: Color = Color { r: 0, g: 255, b: 0, w: 0 };
+pub const C_GREEN_BLUE: Color = Color { r: 0, g: 255, b: 127, w: 0 };
+pub const C_SKY_BLUE: Color = Color { r: 0, g: 255, b: 255, w: 0 };
+pub const C_DEEP_BLUE: Color = Color { r: 0, g: 127, b: 255, w: 0 };
+pub const C_BLUE: Color = Color { r: 0, g: 0, b: 255, w: 0 };
+pub const C_BLUE_PURPLE: Color = Color { r: 127, g: 0, b: 255, w: 0 };
+pub const C_PURPLE: Color = Color { r: 255, g: 0, b: 255, w: 0 };
+pub const C_DARK_PURPLE: Color = Color { r: 255, g: 0, b: 127, w: 0 };
+pub const C_WHITE: Color = Color { r: 255, g: 255, b: 255, w: 0 };
+pub const C_OFF: Color = Color { r: 0, g: 0, b: 0, w: 0 };
+// This only lights the dedicated white LED on RGBW strips, and will be off on RGB strips:
+pub const C_PURE_WHITE: Color = Color { r: 0, g: 0, b: 0, w: 255 };
+pub const C_T_3000K: Color = Color { r: 255, g: 180, b: 107, w: 0 };
+pub const C_T_3500K: Color = Color { r: 255, g: 196, b: 137, w: 0 };
+pub const C_T_4000K: Color = Color { r: 255, g: 209, b: 163, w: 0 };
+pub const C_T_5000K: Color = Color { r: 255, g: 228, b: 206, w: 0 };
+pub const C_CYAN: Color = C_SKY_BLUE;
+pub const C_MAGENTA: Color = C_PURPLE;
+pub const C_PINK: Color = Color { r: 255, g: 64, b: 127, w: 0 };
+pub const C_WARM_WHITE: Color = C_T_3000K;
+pub const C_COOL_WHITE: Color = C_T_5000K;
+
+/// The named colors above in lower case, so a color can be looked up by name at runtime with
+/// `Color::from_name()`, for example from a serial command.
+pub const NAMED_COLORS: &[(&str, Color)] = &[
+    ("red", C_RED),
+    ("orange", C_ORANGE),
+    ("yellow", C_YELLOW),
+    ("yellow green", C_YELLOW_GREEN),
+    ("green", C_GREEN),
+    ("green blue", C_GREEN_BLUE),
+    ("sky blue", C_SKY_BLUE),
+    ("cyan", C_CYAN),
+    ("deep blue", C_DEEP_BLUE),
+    ("blue", C_BLUE),
+    ("blue purple", C_BLUE_PURPLE),
+    ("purple", C_PURPLE),
+    ("magenta", C_MAGENTA),
+    ("dark purple", C_DARK_PURPLE),
+    ("pink", C_PINK),
+    ("white", C_WHITE),
+    ("pure white", C_PURE_WHITE),
+    ("warm white", C_WARM_WHITE),
+    ("cool white", C_COOL_WHITE),
+    ("off", C_OFF),
+];
 
 // Use const generic rainbows to make iterable rainbows of various sizes. Rainbows contain a
 // list of colors in order, which will be used by animations as a color rainbow.
@@ -104,12 +578,63 @@ pub const R_T_3500K: Rainbow = &[C_T_4000K];
 pub const R_T_4000K: Rainbow = &[C_T_4000K];
 pub const R_T_5000K: Rainbow = &[C_T_4000K];
 
+/// A palette is a Rainbow that can be sampled smoothly at any position from 0 to 255, with the
+/// anchor colors spread evenly across the positions. If it wraps, the last anchor blends back
+/// into the first one at the end so the palette can be cycled through without a jump. If it
+/// doesn't wrap, the first anchor is at 0 and the last anchor is at 255.
+#[derive(Copy, Clone)]
+pub struct Palette<'a> {
+    anchors: Rainbow<'a>,
+    wraps: bool,
+}
+
+impl<'a> Palette<'a> {
+    pub const fn new(anchors: Rainbow<'a>, wraps: bool) -> Self {
+        Palette { anchors, wraps }
+    }
+
+    // this returns the color at pos, linearly interpolated between the two nearest anchors.
+    // Sampling exactly at an anchor's position returns that anchor color:
+    pub fn sample(&self, pos: u8) -> Color {
+        let anchor_count = self.anchors.len();
+        let (segment_count, span) = match self.wraps {
+            true => (anchor_count, 256),
+            false => (anchor_count.saturating_sub(1), 255),
+        };
+        if segment_count == 0 {
+            return self.anchors.first().copied().unwrap_or(C_OFF);
+        }
+
+        let position = |anchor: usize| anchor * span / segment_count;
+        let pos = pos as usize;
+        let mut anchor = pos * segment_count / span;
+        if anchor >= segment_count {
+            return self.anchors[anchor_count - 1];
+        }
+        if position(anchor + 1) <= pos {
+            anchor += 1;
+        }
+
+        let (start, end) = (position(anchor), position(anchor + 1));
+        Color::color_lerp(
+            (pos - start) as i32,
+            0,
+            (end - start) as i32,
+            self.anchors[anchor],
+            self.anchors[(anchor + 1) % anchor_count],
+        )
+    }
+}
+
+pub const P_RAINBOW: Palette = Palette::new(R_ROYGBIV, true);
+pub const P_HEAT: Palette = Palette::new(&[C_OFF, C_RED, C_ORANGE, C_YELLOW, C_WHITE], false);
+
 pub const fn dark_pattern(base: Color) -> [Color; 6] {
     let mut colors = [C_OFF; 6];
     let mut i = 0;
     while i < 3 {
-        colors[i * 2] = Color { r: base.r / 2, g: base.g / 2, b: base.b / 2 };
-        colors[i * 2 + 1] = Color { r: base.r / 4, g: base.g / 4, b: base.b / 4 };
+        colors[i * 2] = Color { r: base.r / 2, g: base.g / 2, b: base.b / 2, w: base.w / 2 };
+        colors[i * 2 + 1] = Color { r: base.r / 4, g: base.g / 4, b: base.b / 4, w: base.w / 4 };
         i += 1;
     }
     colors
@@ -163,21 +688,383 @@ pub const RAINBOW_ARRAY: [&[Color]; NUM_RAINBOWS] = [
     R_WHITE_PATTERN,
 ];
 
-/// A color correction table for LEDs to make them look like the color you expect:
-/// Shamelessly stolen from Adafruit's neopixel library somewhere a long time ago.
-pub static GAMMA8: [u8; 256] = [
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1,
-    1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 5, 5, 5,
-    5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 14,
-    14, 15, 15, 16, 16, 17, 17, 18, 18, 19, 19, 20, 20, 21, 21, 22, 22, 23, 24, 24, 25, 25, 26, 27,
-    27, 28, 29, 29, 30, 31, 32, 32, 33, 34, 35, 35, 36, 37, 38, 39, 39, 40, 41, 42, 43, 44, 45, 46,
-    47, 48, 49, 50, 50, 51, 52, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 66, 67, 68, 69, 70, 72,
-    73, 74, 75, 77, 78, 79, 81, 82, 83, 85, 86, 87, 89, 90, 92, 93, 95, 96, 98, 99, 101, 102, 104,
-    105, 107, 109, 110, 112, 114, 115, 117, 119, 120, 122, 124, 126, 127, 129, 131, 133, 135, 137,
-    138, 140, 142, 144, 146, 148, 150, 152, 154, 156, 158, 160, 162, 164, 167, 169, 171, 173, 175,
-    177, 180, 182, 184, 186, 189, 191, 193, 196, 198, 200, 203, 205, 208, 210, 213, 215, 218, 220,
-    223, 225, 228, 231, 233, 236, 239, 241, 244, 247, 249, 252, 255,
-];
+/// A color correction table for LEDs to make them look like the color you expect. It corrects for
+/// a gamma of 2.8, which matches the table in Adafruit's neopixel library, and is built at compile
+/// time by `gamma_table()`.
+pub static GAMMA8: [u8; 256] = gamma_table();
+
+// this works out the gamma table for a gamma of 2.8, rounding each entry to the nearest value.
+// There are no float powers in a const fn, so instead of 255 * (i / 255)^2.8, each entry is the
+// largest n where (n - 0.5)^5 is at most i^14 / 255^9, which is that value to the fifth power.
+// Both sides are multiplied by 32 to keep them whole numbers, and the largest fits in a u128:
+const fn gamma_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let target = 32 * (i as u128).pow(14);
+        let mut n: u128 = 0;
+        while n < 255 && (2 * n + 1).pow(5) * 255_u128.pow(9) <= target {
+            n += 1;
+        }
+        table[i] = n as u8;
+        i += 1;
+    }
+    table
+}
 
 /// This is the first color in the GAMMA8 array that is not completely turned off.
 pub const FIRST_NON_OFF_COLOR: usize = 28;
+
+/// This is a color in the OKLab color space, stored as fixed point numbers where 1.0 is
+/// `OKLAB_ONE`. There is no FPU, so the conversions are done entirely with integer math.
+struct Oklab {
+    l: i64,
+    a: i64,
+    b: i64,
+}
+
+/// This is the fixed point value of 1.0 used by the OKLab conversions.
+const OKLAB_ONE: i64 = 1 << 16;
+
+// this multiplies two fixed point values together, rounding to the nearest value:
+fn fixed_mul(x: i64, y: i64) -> i64 {
+    (x * y + (1 << 15)) >> 16
+}
+
+// this multiplies a fixed point vector by a matrix of fixed point coefficients:
+fn fixed_mat_mul(m: &[[i64; 3]; 3], v: [i64; 3]) -> [i64; 3] {
+    let row = |r: &[i64; 3]| fixed_mul(r[0], v[0]) + fixed_mul(r[1], v[1]) + fixed_mul(r[2], v[2]);
+    [row(&m[0]), row(&m[1]), row(&m[2])]
+}
+
+// this is the integer cube root of x, rounded to the nearest value:
+fn cube_root(x: u64) -> u64 {
+    let root = cube_root_floor(x);
+    match (root + 1).pow(3) - x < x - root.pow(3) {
+        true => root + 1,
+        false => root,
+    }
+}
+
+// this is the integer cube root of x, rounded down:
+fn cube_root_floor(mut x: u64) -> u64 {
+    let mut root = 0_u64;
+    let mut shift = 63;
+    while shift >= 0 {
+        root <<= 1;
+        let b = 3 * root * (root + 1) + 1;
+        if (x >> shift) >= b {
+            x -= b << shift;
+            root += 1;
+        }
+        shift -= 3;
+    }
+    root
+}
+
+impl Oklab {
+    // the conversion matrices from https://bottosson.github.io/posts/oklab/ scaled by OKLAB_ONE:
+    const RGB_TO_LMS: [[i64; 3]; 3] = [
+        [27015, 35149, 3372],
+        [13887, 44610, 7038],
+        [5787, 18463, 41286],
+    ];
+    const LMS_TO_LAB: [[i64; 3]; 3] = [
+        [13792, 52011, -267],
+        [129630, -159160, 29530],
+        [1698, 51300, -52997],
+    ];
+    const LAB_TO_LMS: [[i64; 3]; 3] = [
+        [65536, 25974, 14143],
+        [65536, -6918, -4185],
+        [65536, -5864, -84639],
+    ];
+    const LMS_TO_RGB: [[i64; 3]; 3] = [
+        [267173, -216774, 15137],
+        [-83128, 171033, -22369],
+        [-275, -46099, 111910],
+    ];
+
+    fn from_color(color: Color) -> Self {
+        let linear = |channel: u8| SRGB_TO_LINEAR[channel as usize] as i64;
+        let lms =
+            fixed_mat_mul(&Oklab::RGB_TO_LMS, [linear(color.r), linear(color.g), linear(color.b)]);
+        // the cube root of a fixed point value needs two extra factors of OKLAB_ONE inside it:
+        let lms = lms.map(|x| cube_root((x.max(0) as u64) << 32) as i64);
+        let [l, a, b] = fixed_mat_mul(&Oklab::LMS_TO_LAB, lms);
+        Oklab { l, a, b }
+    }
+
+    fn to_color(&self) -> Color {
+        let lms = fixed_mat_mul(&Oklab::LAB_TO_LMS, [self.l, self.a, self.b]);
+        let lms = lms.map(|x| fixed_mul(fixed_mul(x, x), x));
+        let [r, g, b] = fixed_mat_mul(&Oklab::LMS_TO_RGB, lms);
+        let srgb = |linear: i64| {
+            let linear = linear.clamp(0, OKLAB_ONE - 1) as u16;
+            // find the closest entry in the table to undo the sRGB transfer function:
+            let index = SRGB_TO_LINEAR.partition_point(|&x| x < linear).min(255);
+            match index > 0 && linear - SRGB_TO_LINEAR[index - 1] < SRGB_TO_LINEAR[index] - linear {
+                true => (index - 1) as u8,
+                false => index as u8,
+            }
+        };
+        Color::new(srgb(r), srgb(g), srgb(b))
+    }
+}
+
+/// This converts 8 bit sRGB channel values into linear light, scaled to 0..=65535.
+static SRGB_TO_LINEAR: [u16; 256] = [
+    0, 20, 40, 60, 80, 99, 119, 139, 159, 179, 199, 219, 241, 264, 288, 313, 340, 367, 396, 427,
+    458, 491, 526, 562, 599, 637, 677, 718, 761, 805, 851, 898, 947, 997, 1048, 1101, 1156, 1212,
+    1270, 1330, 1391, 1453, 1517, 1583, 1651, 1720, 1790, 1863, 1937, 2013, 2090, 2170, 2250, 2333,
+    2418, 2504, 2592, 2681, 2773, 2866, 2961, 3058, 3157, 3258, 3360, 3464, 3570, 3678, 3788, 3900,
+    4014, 4129, 4247, 4366, 4488, 4611, 4736, 4864, 4993, 5124, 5257, 5392, 5530, 5669, 5810, 5953,
+    6099, 6246, 6395, 6547, 6700, 6856, 7014, 7174, 7335, 7500, 7666, 7834, 8004, 8177, 8352, 8528,
+    8708, 8889, 9072, 9258, 9445, 9635, 9828, 10022, 10219, 10417, 10619, 10822, 11028, 11235,
+    11446, 11658, 11873, 12090, 12309, 12530, 12754, 12980, 13209, 13440, 13673, 13909, 14146,
+    14387, 14629, 14874, 15122, 15371, 15623, 15878, 16135, 16394, 16656, 16920, 17187, 17456,
+    17727, 18001, 18277, 18556, 18837, 19121, 19407, 19696, 19987, 20281, 20577, 20876, 21177,
+    21481, 21787, 22096, 22407, 22721, 23038, 23357, 23678, 24002, 24329, 24658, 24990, 25325,
+    25662, 26001, 26344, 26688, 27036, 27386, 27739, 28094, 28452, 28813, 29176, 29542, 29911,
+    30282, 30656, 31033, 31412, 31794, 32179, 32567, 32957, 33350, 33745, 34143, 34544, 34948,
+    35355, 35764, 36176, 36591, 37008, 37429, 37852, 38278, 38706, 39138, 39572, 40009, 40449,
+    40891, 41337, 41785, 42236, 42690, 43147, 43606, 44069, 44534, 45002, 45473, 45947, 46423,
+    46903, 47385, 47871, 48359, 48850, 49344, 49841, 50341, 50844, 51349, 51858, 52369, 52884,
+    53401, 53921, 54445, 54971, 55500, 56032, 56567, 57105, 57646, 58190, 58737, 59287, 59840,
+    60396, 60955, 61517, 62082, 62650, 63221, 63795, 64372, 64952, 65535,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(color: Color) -> [u8; 3] {
+        [color.r, color.g, color.b]
+    }
+
+    #[test]
+    fn hue_sweep_is_smooth_and_monotonic() {
+        let hue_color = |h: u16| channels(Hsv::new(h, 255, 255).to_color());
+
+        // neighbouring hues never jump by more than one sextant step, including across the wrap:
+        for h in 0..360 {
+            let (color, next) = (hue_color(h), hue_color(h + 1));
+            for (a, b) in color.iter().zip(next.iter()) {
+                assert!(a.abs_diff(*b) <= 5, "hue {} jumps from {:?} to {:?}", h, color, next);
+            }
+        }
+        assert_eq!(hue_color(360), hue_color(0));
+
+        // within each sextant every channel only rises, only falls, or stays the same:
+        for sextant in 0..6 {
+            let hues = sextant * 60..=sextant * 60 + 60;
+            for channel in 0..3 {
+                let values: [u8; 61] =
+                    core::array::from_fn(|i| hue_color(sextant * 60 + i as u16)[channel]);
+                let is_rising = values.windows(2).all(|pair| pair[0] <= pair[1]);
+                let is_falling = values.windows(2).all(|pair| pair[0] >= pair[1]);
+                assert!(
+                    is_rising || is_falling,
+                    "channel {} is not monotonic over {:?}",
+                    channel,
+                    hues
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn to_hsv_reverses_to_color() {
+        for h in (0..360).step_by(60) {
+            let hsv = Hsv::new(h, 255, 255).to_color().to_hsv();
+            assert_eq!((hsv.h, hsv.s, hsv.v), (h, 255, 255));
+        }
+    }
+
+    #[test]
+    fn hex_strings_parse_in_either_case() {
+        assert_eq!(Color::from_hex_str("#FF0000"), Some(C_RED));
+        assert_eq!(Color::from_hex_str("00ff00"), Some(C_GREEN));
+        assert_eq!(Color::from_hex_str("#0000Ff"), Some(C_BLUE));
+        assert_eq!(Color::from_u32(0xFFFF00), C_YELLOW);
+    }
+
+    #[test]
+    fn malformed_hex_strings_are_rejected() {
+        for hex in [
+            "zzz", "", "#", "#FF000", "#FF00000", "FF00GG", "##FF0000", "+FF000",
+        ] {
+            assert_eq!(Color::from_hex_str(hex), None, "{} was accepted", hex);
+        }
+    }
+
+    #[test]
+    fn operators_saturate_like_the_named_methods() {
+        assert_eq!(C_RED + C_GREEN, C_YELLOW);
+        assert_eq!(Color::new(200, 100, 0) + Color::new(100, 100, 10), Color::new(255, 200, 10));
+        assert_eq!(Color::new(50, 200, 0) - Color::new(100, 100, 10), Color::new(0, 100, 0));
+        assert_eq!(C_WHITE * 255, C_WHITE);
+        assert_eq!(Color::new(200, 100, 50) * 128, Color::new(200, 100, 50).scale(128));
+    }
+
+    #[test]
+    fn clamped_lerp_stops_at_the_end_colors() {
+        assert_eq!(Color::color_lerp_clamped(150, 0, 100, C_OFF, C_WHITE), C_WHITE);
+        assert_eq!(Color::color_lerp_clamped(-20, 0, 100, C_OFF, C_WHITE), C_OFF);
+        assert_eq!(Color::color_lerp_clamped(150, 100, 0, C_OFF, C_WHITE), C_OFF);
+        assert_eq!(
+            Color::color_lerp_clamped(50, 0, 100, C_OFF, C_WHITE),
+            Color::color_lerp(50, 0, 100, C_OFF, C_WHITE)
+        );
+    }
+
+    #[test]
+    fn palette_samples_anchors_exactly() {
+        let anchors = [C_RED, C_GREEN, C_BLUE, C_WHITE];
+        let wrapping = Palette::new(&anchors, true);
+        for (index, &anchor) in anchors.iter().enumerate() {
+            assert_eq!(wrapping.sample((index * 64) as u8), anchor);
+        }
+
+        let clamped = Palette::new(&anchors, false);
+        assert_eq!(clamped.sample(0), C_RED);
+        assert_eq!(clamped.sample(85), C_GREEN);
+        assert_eq!(clamped.sample(170), C_BLUE);
+        assert_eq!(clamped.sample(255), C_WHITE);
+    }
+
+    #[test]
+    fn rotating_red_by_120_degrees_is_green() {
+        assert_eq!(C_RED.rotate_hue(120), C_GREEN);
+        assert_eq!(C_RED.rotate_hue(240), C_BLUE);
+        assert_eq!(C_RED.rotate_hue(360), C_RED);
+        // grays have no hue to rotate:
+        assert_eq!(Color::new(90, 90, 90).rotate_hue(120), Color::new(90, 90, 90));
+    }
+
+    #[test]
+    fn named_colors_are_found_by_name() {
+        assert_eq!(Color::from_name("cyan"), Some(Color::new(0, 255, 255)));
+        assert_eq!(Color::from_name("Warm White"), Some(C_WARM_WHITE));
+        assert_eq!(Color::from_name("chartreuse"), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn colors_round_trip_through_postcard() {
+        let colors = [C_RED, Color::new_rgbw(1, 2, 3, 4), C_OFF];
+        let mut buffer = [0; 32];
+        let bytes = postcard::to_slice(&colors, &mut buffer).unwrap();
+        let decoded: [Color; 3] = postcard::from_bytes(bytes).unwrap();
+        assert_eq!(decoded, colors);
+    }
+
+    #[test]
+    fn gamma_table_keeps_the_ends_and_never_decreases() {
+        assert_eq!(GAMMA8[0], 0);
+        assert_eq!(GAMMA8[255], 255);
+        assert!(GAMMA8.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(GAMMA8[FIRST_NON_OFF_COLOR - 1], 0);
+        assert_eq!(GAMMA8[FIRST_NON_OFF_COLOR], 1);
+        assert_eq!(C_WHITE.gamma_correct(), C_WHITE);
+        assert_eq!(Color::new(128, 64, 0).gamma_correct(), Color::new(37, 5, 0));
+    }
+
+    #[test]
+    fn scaling_keeps_full_and_turns_off_at_zero() {
+        let color = Color::new_rgbw(200, 100, 1, 255);
+        assert_eq!(color.scale(255), color);
+        assert_eq!(color.scale(0), C_OFF);
+        assert_eq!(color.scale(128), Color::new_rgbw(100, 50, 0, 128));
+    }
+
+    #[test]
+    fn dimming_stops_at_zero() {
+        let color = Color::new_rgbw(200, 100, 1, 0);
+        assert_eq!(color.dim(0), color);
+        assert_eq!(color.dim(50), Color::new_rgbw(150, 50, 0, 0));
+        assert_eq!(color.dim(255), C_OFF);
+    }
+
+    #[test]
+    fn adding_clamps_at_full_instead_of_wrapping() {
+        let base = Color::new_rgbw(200, 10, 0, 255);
+        assert_eq!(
+            base.blend_add(&Color::new_rgbw(100, 20, 0, 1)),
+            Color::new_rgbw(255, 30, 0, 255)
+        );
+        assert_eq!(base.blend(&C_OFF, BlendMode::Add), base);
+    }
+
+    #[test]
+    fn max_keeps_the_brightest_of_each_channel() {
+        let base = Color::new_rgbw(200, 10, 0, 5);
+        let other = Color::new_rgbw(100, 20, 0, 4);
+        assert_eq!(base.blend_max(&other), Color::new_rgbw(200, 20, 0, 5));
+        assert_eq!(other.blend(&base, BlendMode::Max), base.blend_max(&other));
+    }
+
+    #[test]
+    fn const_colors_equal_the_named_colors() {
+        const MY_RED: Color = Color::new(255, 0, 0);
+        assert_eq!(C_RED, MY_RED);
+        assert_eq!(Color::OFF, C_OFF);
+        assert_eq!(Color::WHITE, Color::new(255, 255, 255));
+        assert_ne!(C_RED, C_GREEN);
+    }
+
+    #[test]
+    fn random_colors_repeat_for_a_seed_and_random_hues_are_vivid() {
+        use rand::{rngs::SmallRng, SeedableRng};
+
+        let (mut first, mut second) = (SmallRng::seed_from_u64(7), SmallRng::seed_from_u64(7));
+        for _ in 0..20 {
+            assert_eq!(Color::random(&mut first), Color::random(&mut second));
+            assert_eq!(Color::random_hue(&mut first), Color::random_hue(&mut second));
+        }
+
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let hsv = Color::random_hue(&mut rng).to_hsv();
+            assert!(hsv.s >= 250 && hsv.v == 255, "{:?} is not vivid", hsv);
+        }
+    }
+
+    #[test]
+    fn ease_in_lags_behind_linear_halfway() {
+        let (start, end) = (C_OFF, Color::new_rgbw(200, 200, 200, 200));
+        let linear = Color::lerp_channels(start, end, 128, [Curve::Linear; 4]);
+        let ease_in = Color::lerp_channels(start, end, 128, [Curve::EaseIn; 4]);
+        assert_eq!(linear, Color::new_rgbw(100, 100, 100, 100));
+        assert!(ease_in.r < linear.r);
+
+        // each channel follows its own curve:
+        let mixed = [Curve::Linear, Curve::EaseIn, Curve::EaseOut, Curve::Linear];
+        let color = Color::lerp_channels(start, end, 128, mixed);
+        assert!(color.g < color.r && color.r < color.b);
+        assert_eq!(Color::lerp_channels(start, end, 255, mixed), end);
+    }
+
+    #[test]
+    fn green_looks_brighter_than_blue() {
+        assert!(C_GREEN.luminance() > C_BLUE.luminance());
+        assert!(C_RED.luminance() > C_BLUE.luminance());
+        assert_eq!(C_OFF.luminance(), 0);
+        assert!(C_WHITE.luminance() >= 254);
+
+        let gray = C_GREEN.to_grayscale();
+        assert_eq!((gray.r, gray.g, gray.b), (149, 149, 149));
+    }
+
+    #[test]
+    fn off_and_near_colors_are_recognised() {
+        assert!(C_OFF.is_off());
+        assert!(!Color::new(0, 0, 1).is_off());
+        assert!(!Color::new_rgbw(0, 0, 0, 1).is_off());
+
+        let color = Color::new(100, 150, 200);
+        assert!(color.is_near(&color, 0));
+        assert!(color.is_near(&Color::new(101, 149, 200), 1));
+        assert!(!color.is_near(&Color::new(102, 150, 200), 1));
+    }
+}
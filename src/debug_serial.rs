@@ -0,0 +1,23 @@
+use bl602_hal as hal;
+use core::fmt::Write;
+use embedded_time::rate::*;
+use hal::{clock::Clocks, gpio::*, pac, serial::*};
+
+// this sets up UART0 on pins 16 and 7 for printing debug messages over the USB serial port. It is
+// only available with the debug-serial feature, so builds without it leave those pins free:
+pub fn init_usb_serial<MODE>(
+    uart: pac::UART,
+    clocks: Clocks,
+    baud_rate: Baud,
+    tx_pin: Pin16<MODE>,
+    rx_pin: Pin7<MODE>,
+    tx_mux: UartMux0<Uart0Cts>,
+    rx_mux: UartMux7<Uart0Cts>,
+) -> impl Write {
+    let tx = tx_pin.into_uart_sig0();
+    let rx = rx_pin.into_uart_sig7();
+    let tx_mux = tx_mux.into_uart0_tx();
+    let rx_mux = rx_mux.into_uart0_rx();
+
+    Serial::uart0(uart, Config::default().baudrate(baud_rate), ((tx, tx_mux), (rx, rx_mux)), clocks)
+}
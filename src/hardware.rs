@@ -1,29 +1,73 @@
+use crate::leds::ws28xx::{check_pin_count, PhysicalStrip, StripConfigError, MIN_PULSE_NS};
+#[cfg(feature = "wfi-timer")]
+use bl602_hal::interrupts::{disable_interrupt, enable_interrupt, Interrupt};
 use bl602_hal::timer::{ConfiguredTimerChannel0, ConfiguredTimerChannel1, Preload};
-use core::convert::Infallible;
 use embedded_hal::digital::blocking::OutputPin;
 use embedded_time::duration::*;
 
-pub type DynamicPin<'a> = &'a mut dyn OutputPin<Error = Infallible>;
+pub type DynamicPin<'a> = &'a mut dyn StripPin;
 
-pub struct HardwareController<'a, T>
+/// An output pin that a strip can be sent on. This is implemented for every OutputPin, whatever
+/// its error type is, so pins behind a fallible driver like an I2C port expander can be used as
+/// well as the BL602's own GPIO pins. Errors are ignored, since a failed write in the middle of a
+/// frame can't be taken back, and the strip will just show a glitched frame until the next one.
+/// Expanders are much too slow for the WS2812 timings, so they're only really useful for slow
+/// strips like the WS2811.
+pub trait StripPin {
+    fn set_low(&mut self);
+    fn set_high(&mut self);
+}
+
+impl<P> StripPin for P
+where
+    P: OutputPin,
 {
+    fn set_low(&mut self) {
+        OutputPin::set_low(self).ok();
+    }
+
+    fn set_high(&mut self) {
+        OutputPin::set_high(self).ok();
+    }
+}
+
+/// This holds the output pins for the strips and the timer used to send data to them. Any number
+/// of pins can be used, and the pin at each index drives the strip at the same index of the
+/// LogicalStrip.
+pub struct HardwareController<'a, T> {
     pins: &'a mut [DynamicPin<'a>],
     timer: T,
 }
 
-impl <'a, T> HardwareController<'a, T> {
+impl<'a, T> HardwareController<'a, T> {
     pub fn new(pins: &'a mut [DynamicPin<'a>], timer: T) -> Self {
         HardwareController { pins, timer }
     }
 
+    // this works the same as new(), but first checks that there is a pin for every strip with
+    // leds, since each strip is sent on the pin with the same index:
+    pub fn try_new(
+        pins: &'a mut [DynamicPin<'a>],
+        timer: T,
+        strips: &[PhysicalStrip],
+    ) -> Result<Self, StripConfigError> {
+        check_pin_count(strips, pins.len())?;
+        Ok(HardwareController::new(pins, timer))
+    }
+
+    pub fn pin_count(&self) -> usize {
+        self.pins.len()
+    }
+
+    // these panic if there is no pin at the index. Use try_new() to check the pins against the
+    // strips when the controller is made instead:
     pub fn set_low(&mut self, pin: usize) {
-        self.pins[pin].set_low().ok();
+        self.pins[pin].set_low();
     }
 
     pub fn set_high(&mut self, pin: usize) {
-        self.pins[pin].set_high().ok();
+        self.pins[pin].set_high();
     }
-
 }
 
 impl<'a, T> PeriodicTimer for HardwareController<'a, T>
@@ -38,27 +82,122 @@ where
         self.timer.periodic_wait();
     }
 
+    fn periodic_sleep(&mut self) {
+        self.timer.periodic_sleep();
+    }
+
     fn periodic_check_timeout(&mut self) -> Result<(), TimerError> {
         self.timer.periodic_check_timeout()
     }
+
+    fn set_marks(
+        &mut self,
+        first: impl Into<Nanoseconds<u64>>,
+        second: impl Into<Nanoseconds<u64>>,
+    ) {
+        self.timer.set_marks(first, second);
+    }
+
+    fn wait_for_mark(&mut self, mark: TimerMark) {
+        self.timer.wait_for_mark(mark);
+    }
 }
 
 pub trait PeriodicTimer {
     fn periodic_start(&mut self, time: impl Into<Nanoseconds<u64>>);
     fn periodic_wait(&mut self);
     fn periodic_check_timeout(&mut self) -> Result<(), TimerError>;
+
+    // these time two points part way through every period, so something can be done at a finer
+    // resolution than the period itself, like ending the high time of a bit. set_marks() sets how
+    // long after the start of each period the marks are, and must be called after
+    // periodic_start(). wait_for_mark() blocks until that mark has passed in the current period:
+    fn set_marks(
+        &mut self,
+        first: impl Into<Nanoseconds<u64>>,
+        second: impl Into<Nanoseconds<u64>>,
+    );
+    fn wait_for_mark(&mut self, mark: TimerMark);
+
+    // this waits the same as periodic_wait(), but is allowed to put the CPU to sleep until the
+    // timer fires. Waking up takes too long for the bit timing, so it is only for long waits like
+    // a whole frame:
+    fn periodic_sleep(&mut self) {
+        self.periodic_wait();
+    }
 }
 
 pub enum TimerError {
     WouldBlock,
 }
 
+/// One of the two points in each period of a PeriodicTimer set with `set_marks()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimerMark {
+    First,
+    Second,
+}
+
+/// This keeps a main loop running at a fixed frame rate, so animations move at the same speed no
+/// matter how long each frame takes to draw. The timer keeps running while the frame is being
+/// drawn, so the time spent drawing is taken out of the wait rather than added to it. If a frame
+/// takes longer than the frame period, the next wait returns straight away. The timer can't be
+/// shared with the HardwareController, since sending restarts its timer at the bit rate.
+pub struct FrameClock<T> {
+    timer: T,
+    fps: u16,
+}
+
+impl<T> FrameClock<T>
+where
+    T: PeriodicTimer,
+{
+    pub fn new(timer: T, fps: u16) -> Self {
+        let mut frame_clock = FrameClock { timer, fps };
+        frame_clock.set_fps(fps);
+        frame_clock
+    }
+
+    pub fn fps(&self) -> u16 {
+        self.fps
+    }
+
+    // this changes the frame rate and restarts the current frame. A frame rate of 0 is treated
+    // as 1 frame per second:
+    pub fn set_fps(&mut self, fps: u16) {
+        self.fps = fps.max(1);
+        let frame_period = 1_000_000_000_u64 / self.fps as u64;
+        self.timer.periodic_start(frame_period.nanoseconds());
+    }
+
+    // this blocks until the start of the next frame. Call it once every time round the loop. With
+    // the wfi-timer feature, the CPU sleeps until then instead of polling the timer:
+    pub fn wait_for_next_frame(&mut self) {
+        self.timer.periodic_sleep();
+    }
+}
+
+// this clamps a timer period up to MIN_PULSE_NS, the shortest period the timer can honor:
+fn clamp_period(time: Nanoseconds<u64>) -> Nanoseconds<u64> {
+    Nanoseconds(time.0.max(MIN_PULSE_NS as u64))
+}
+
+// Periods shorter than MIN_PULSE_NS are clamped up to it, since a match value of 0 or one only a
+// few timer clocks long can't be honored and would make periodic_wait() unpredictable. The marks
+// use the match0 and match1 comparators, which are checked against the same counter that match2
+// resets at the end of every period, so they are timed to a single clock of the timer. Their
+// status is cleared at the end of every period, so a mark that wasn't waited for can't end the
+// wait for it in the next period early.
+// periodic_wait() always busy-polls the timer match, since it is used for the bit timing. With the
+// wfi-timer feature enabled, periodic_sleep() instead sleeps with wfi until the match2 interrupt
+// is pending, which uses less power for long waits. No interrupt handler is installed, so the
+// interrupt is only enabled in the interrupt controller for the length of the sleep, and global
+// interrupts are held off meanwhile so it only wakes the CPU and never traps:
 macro_rules! setup_periodic_timer {
-    ($timer:ident) => {
+    ($timer:ident, $interrupt:ident) => {
         impl PeriodicTimer for $timer {
             fn periodic_start(&mut self, time: impl Into<Nanoseconds<u64>>) {
-                let time: Nanoseconds<u64> = time.into();
-                self.set_match2(time);
+                self.set_match2(clamp_period(time.into()));
                 self.enable_match2_interrupt();
                 self.set_preload_value(0.nanoseconds());
                 self.set_preload(Preload::PreloadMatchComparator2);
@@ -69,22 +208,153 @@ macro_rules! setup_periodic_timer {
                 loop {
                     if self.is_match2() {
                         self.clear_match2_interrupt();
+                        self.clear_match0_interrupt();
+                        self.clear_match1_interrupt();
                         break;
                     }
                 }
             }
 
+            #[cfg(feature = "wfi-timer")]
+            fn periodic_sleep(&mut self) {
+                riscv::interrupt::free(|_| {
+                    enable_interrupt(Interrupt::$interrupt);
+                    // sleep until the match interrupt is pending instead of spinning:
+                    while !self.is_match2() {
+                        unsafe {
+                            riscv::asm::wfi();
+                        }
+                    }
+                    disable_interrupt(Interrupt::$interrupt);
+                    self.clear_match2_interrupt();
+                });
+            }
+
             fn periodic_check_timeout(&mut self) -> Result<(), TimerError> {
                 if self.is_match2() {
                     self.clear_match2_interrupt();
+                    self.clear_match0_interrupt();
+                    self.clear_match1_interrupt();
                     return Ok(());
                 } else {
                     return Err(TimerError::WouldBlock);
                 }
             }
+
+            fn set_marks(
+                &mut self,
+                first: impl Into<Nanoseconds<u64>>,
+                second: impl Into<Nanoseconds<u64>>,
+            ) {
+                self.set_match0(first.into());
+                self.set_match1(second.into());
+                self.enable_match0_interrupt();
+                self.enable_match1_interrupt();
+                self.clear_match0_interrupt();
+                self.clear_match1_interrupt();
+            }
+
+            fn wait_for_mark(&mut self, mark: TimerMark) {
+                match mark {
+                    TimerMark::First => while !self.is_match0() {},
+                    TimerMark::Second => while !self.is_match1() {},
+                }
+            }
         }
     };
 }
 
-setup_periodic_timer!(ConfiguredTimerChannel0);
-setup_periodic_timer!(ConfiguredTimerChannel1);
+setup_periodic_timer!(ConfiguredTimerChannel0, TimerCh0);
+setup_periodic_timer!(ConfiguredTimerChannel1, TimerCh1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leds::ws28xx::StripTimings;
+
+    /// A pin behind a driver that can fail, like an I2C port expander, that counts every write
+    /// it is asked to make.
+    struct ExpanderPin {
+        writes: usize,
+    }
+
+    #[derive(Debug)]
+    struct BusError;
+
+    impl OutputPin for ExpanderPin {
+        type Error = BusError;
+
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.writes += 1;
+            Err(BusError)
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fallible_pins_can_drive_a_strip() {
+        let mut expander_pin = ExpanderPin { writes: 0 };
+        {
+            let mut pins: [DynamicPin; 1] = [&mut expander_pin];
+            let mut hc = HardwareController::new(&mut pins, ());
+            hc.set_high(0);
+            // the error from the expander is ignored rather than panicking:
+            hc.set_low(0);
+        }
+        assert_eq!(expander_pin.writes, 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn frames_stay_evenly_spaced_however_long_they_take() {
+        use crate::mock::{MockClock, MockTimer};
+
+        let clock = MockClock::new();
+        let mut frame_clock = FrameClock::new(MockTimer::new(&clock), 100);
+        let frame_ns = 10_000_000;
+
+        let mut frame_start_ns = clock.now_ns();
+        for work_ns in [1_000_000, 9_000_000, 0, 4_500_000, 7_000_000] {
+            clock.advance(work_ns);
+            frame_clock.wait_for_next_frame();
+            assert_eq!(clock.now_ns() - frame_start_ns, frame_ns);
+            frame_start_ns = clock.now_ns();
+        }
+
+        // a frame that runs long doesn't wait, and the next one catches back up:
+        clock.advance(15_000_000);
+        frame_clock.wait_for_next_frame();
+        assert_eq!(clock.now_ns() - frame_start_ns, 15_000_000);
+        frame_clock.wait_for_next_frame();
+        assert_eq!(clock.now_ns() - frame_start_ns, 2 * frame_ns);
+
+        frame_clock.set_fps(0);
+        assert_eq!(frame_clock.fps(), 1);
+    }
+
+    #[test]
+    fn tiny_periods_are_clamped() {
+        assert_eq!(clamp_period(Nanoseconds(0)).0, MIN_PULSE_NS as u64);
+        assert_eq!(clamp_period(Nanoseconds(1)).0, MIN_PULSE_NS as u64);
+        assert_eq!(clamp_period(Nanoseconds(1250)).0, 1250);
+
+        // timings that are too fast for the timer are clamped the same way:
+        let tiny = StripTimings { zero_h: 0, one_h: 1, full_cycle: 2, reset_us: 1 };
+        let one = tiny.one_symbol();
+        assert_eq!((one.high_ns, one.low_ns), (MIN_PULSE_NS, MIN_PULSE_NS));
+        assert_eq!(tiny.reset_periods_at(0), 1000_u32.div_ceil(MIN_PULSE_NS));
+    }
+
+    fn is_periodic_timer<T: PeriodicTimer>() {}
+
+    #[test]
+    fn either_timer_channel_can_drive_the_strips() {
+        is_periodic_timer::<ConfiguredTimerChannel0>();
+        is_periodic_timer::<ConfiguredTimerChannel1>();
+        is_periodic_timer::<HardwareController<'static, ConfiguredTimerChannel1>>();
+    }
+}
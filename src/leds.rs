@@ -1,28 +1,160 @@
 pub mod ws28xx {
     use crate::{
         colors as c,
-        hardware::{HardwareController, PeriodicTimer},
+        hardware::{HardwareController, PeriodicTimer, TimerMark},
     };
     use bitvec::prelude::*;
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use core::task::{Context, Poll};
+    use embedded_hal::spi::blocking::Write;
     use embedded_time::duration::*;
+    #[cfg(target_arch = "riscv32")]
+    use riscv::register::mcycle;
 
+    /// The high times and full cycle time are in nanoseconds. The reset time is how long the data
+    /// line is held low before each frame so the leds latch the previous frame and start over,
+    /// and it is in microseconds. If it is too short, the first led of the strip can glitch.
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct StripTimings {
         pub zero_h: u32,
         pub one_h: u32,
         pub full_cycle: u32,
+        pub reset_us: u32,
     }
 
     #[allow(unused_variables)]
     impl StripTimings {
-        pub const WS2811_ADAFRUIT: StripTimings =
-            StripTimings { zero_h: 500_u32, one_h: 1200_u32, full_cycle: 2500_u32 };
-        pub const WS2812_ADAFRUIT: StripTimings =
-            StripTimings { zero_h: 400_u32, one_h: 800_u32, full_cycle: 1250_u32 };
+        pub const WS2811_ADAFRUIT: StripTimings = StripTimings {
+            zero_h: 500_u32,
+            one_h: 1200_u32,
+            full_cycle: 2500_u32,
+            reset_us: 300_u32,
+        };
+        pub const WS2812_ADAFRUIT: StripTimings = StripTimings {
+            zero_h: 400_u32,
+            one_h: 800_u32,
+            full_cycle: 1250_u32,
+            reset_us: 300_u32,
+        };
+        /// The 12V WS2815 has shorter high times than the WS2812 and needs a reset of at least
+        /// 280us. Its datasheet allows a zero high time of 220ns to 380ns and a one high time of
+        /// 580ns to 1us. It takes its colors in GRB order.
+        pub const WS2815: StripTimings = StripTimings {
+            zero_h: 300_u32,
+            one_h: 750_u32,
+            full_cycle: 1250_u32,
+            reset_us: 300_u32,
+        };
+        /// The SK6812 comes in RGB and RGBW versions, usually in GRB or GRBW order. Its datasheet
+        /// allows 150ns either side of these high times, and it only needs a reset of at least
+        /// 80us.
+        pub const SK6812: StripTimings = StripTimings {
+            zero_h: 300_u32,
+            one_h: 600_u32,
+            full_cycle: 1250_u32,
+            reset_us: 100_u32,
+        };
+        // There is no preset for the APA102, since it has separate clock and data lines instead of
+        // a single timed data line. It needs to be driven by an SPI peripheral directly.
+
+        // this is how long the data line is high and then low for to send a zero bit:
+        pub fn zero_symbol(&self) -> SymbolTimes {
+            self.symbol(self.zero_h)
+        }
+
+        // this is how long the data line is high and then low for to send a one bit:
+        pub fn one_symbol(&self) -> SymbolTimes {
+            self.symbol(self.one_h)
+        }
+
+        // the high time is clamped so the pin is always high and low for at least MIN_PULSE_NS:
+        fn symbol(&self, high_time: u32) -> SymbolTimes {
+            let full_cycle = self.full_cycle.max(2 * MIN_PULSE_NS);
+            let high_ns = high_time.clamp(MIN_PULSE_NS, full_cycle - MIN_PULSE_NS);
+            SymbolTimes { high_ns, low_ns: full_cycle - high_ns }
+        }
+
+        // this is how many bit periods the data line needs to be held low for to reset the leds,
+        // rounded up so the reset is never shorter than reset_us:
+        pub fn reset_periods(&self) -> u32 {
+            self.reset_periods_at(self.full_cycle)
+        }
+
+        // this checks that a reset that lasted reset_cycles CPU cycles at sysclk_hz was at least
+        // reset_us long, logging a warning if it wasn't. A reset that is too short can make the
+        // first led show garbage. Use it with last_reset_cycles() to check the real reset time:
+        pub fn check_reset_cycles(&self, reset_cycles: usize, sysclk_hz: u32) -> bool {
+            let reset_ns = reset_cycles as u64 * 1_000_000_000 / sysclk_hz as u64;
+            let is_long_enough = reset_ns >= self.reset_us as u64 * 1000;
+            #[cfg(feature = "defmt")]
+            if !is_long_enough {
+                defmt::warn!("reset took {}ns, needs {}us", reset_ns, self.reset_us);
+            }
+            is_long_enough
+        }
+
+        // this is the same as reset_periods(), but for a timer running at a different period:
+        pub fn reset_periods_at(&self, period: u32) -> u32 {
+            (self.reset_us * 1000).div_ceil(period.max(MIN_PULSE_NS))
+        }
+    }
+
+    /// How long the data line is held high and then low for to send one bit, in nanoseconds.
+    /// This is worked out from the StripTimings. The timer period is a whole bit and the end of
+    /// the high time is a mark within it, so any duty cycle can be sent to within a single clock
+    /// of the timer.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct SymbolTimes {
+        pub high_ns: u32,
+        pub low_ns: u32,
+    }
+
+    // this is how many CPU cycles the data line was held low for before the last strip sent by
+    // send_bits() started, measured with mcycle:
+    static LAST_RESET_CYCLES: AtomicUsize = AtomicUsize::new(0);
+
+    // this is the length of the last reset sent by send_bits() in CPU cycles, which can be checked
+    // with StripTimings::check_reset_cycles(). The cycle counter only exists on the chip, so this
+    // is always 0 when built for the host:
+    pub fn last_reset_cycles() -> usize {
+        LAST_RESET_CYCLES.load(Ordering::Relaxed)
     }
 
-    pub const WS2811_DELAY_LOOPS_BEFORE_SEND: u32 = 900;
+    /// This is the shortest time the bit sending loop can reliably hold the data line high or low
+    /// for, since it has to get from one timer event to the next in that time. The high and low
+    /// times of every bit are kept at least this long, and timer periods are never shorter.
+    pub const MIN_PULSE_NS: u32 = 200;
+
+    /// The SPI clock rate that `send_via_spi` expects. Each data bit is sent as 3 SPI bits of
+    /// about 417ns each, so a 0 is high for 417ns and a 1 is high for 833ns, which is within the
+    /// tolerances of both the WS2811 in high speed mode and the WS2812.
+    pub const SPI_BITS_PER_SECOND: u32 = 2_400_000;
+
+    /// This is the number of SPI bytes sent for every byte of color data.
+    pub const SPI_BYTES_PER_BYTE: usize = 3;
 
+    /// This many zero bytes are sent before each frame over SPI to hold the data line low for
+    /// 300us, which is long enough for any of the supported strips to reset.
+    pub const SPI_RESET_BYTE_COUNT: usize = 90;
+
+    /// This is returned when sending a strip over SPI fails.
+    #[derive(Debug)]
+    pub enum SpiSendError<E> {
+        /// The SPI buffer can't hold the expanded data for the strip.
+        BufferTooSmall { needed: usize, length: usize },
+        /// The SPI peripheral returned an error.
+        Spi(E),
+    }
+
+    /// The order the color channels are sent to the strip in. The variants ending in W are for
+    /// RGBW strips that take a fourth byte per LED for a dedicated white LED.
     #[allow(clippy::upper_case_acronyms)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub enum ColorOrder {
         RGB,
         RBG,
@@ -30,30 +162,87 @@ pub mod ws28xx {
         GBR,
         BRG,
         BGR,
+        RGBW,
+        RBGW,
+        GRBW,
+        GBRW,
+        BRGW,
+        BGRW,
     }
 
     impl ColorOrder {
-        pub fn offsets(&self) -> [usize; 3] {
+        // this returns the byte offsets of the r, g, b, and w channels in the order above. The w
+        // offset is only used for RGBW strips:
+        pub fn offsets(&self) -> [usize; 4] {
+            use ColorOrder::*;
+            match self {
+                RGB | RGBW => [0, 1, 2, 3],
+                RBG | RBGW => [0, 2, 1, 3],
+                GRB | GRBW => [1, 0, 2, 3],
+                BRG | BRGW => [1, 2, 0, 3],
+                GBR | GBRW => [2, 0, 1, 3],
+                BGR | BGRW => [2, 1, 0, 3],
+            }
+        }
+
+        // this is the number of bytes sent to the strip for each LED:
+        pub const fn bytes_per_led(&self) -> usize {
             use ColorOrder::*;
             match self {
-                RGB => [0, 1, 2],
-                RBG => [0, 2, 1],
-                GRB => [1, 0, 2],
-                BRG => [1, 2, 0],
-                GBR => [2, 0, 1],
-                BGR => [2, 1, 0],
+                RGB | RBG | GRB | GBR | BRG | BGR => 3,
+                RGBW | RBGW | GRBW | GBRW | BRGW | BGRW => 4,
             }
         }
     }
 
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct PhysicalStrip {
         pub led_count: usize,
         pub reversed: bool,
         pub color_order: ColorOrder,
         pub strip_timings: StripTimings,
+        pub brightness: u8,
+        pub inverted: bool,
     }
 
     impl PhysicalStrip {
+        // this starts building a strip of led_count leds with the defaults for a WS2812 strip, so
+        // only the settings that differ need to be given. It can be used in consts:
+        pub const fn builder(led_count: usize) -> PhysicalStripBuilder {
+            PhysicalStripBuilder {
+                strip: PhysicalStrip {
+                    led_count,
+                    reversed: false,
+                    color_order: ColorOrder::GRB,
+                    strip_timings: StripTimings::WS2812_ADAFRUIT,
+                    brightness: u8::MAX,
+                    inverted: false,
+                },
+            }
+        }
+
+        // these set the level of the data line at the leds. If the strip is inverted, for example
+        // by an inverting level shifter, the pin is driven the opposite way:
+        fn set_data_high<T>(&self, hc: &mut HardwareController<T>, pin_index: usize) {
+            match self.inverted {
+                true => hc.set_low(pin_index),
+                false => hc.set_high(pin_index),
+            }
+        }
+
+        fn set_data_low<T>(&self, hc: &mut HardwareController<T>, pin_index: usize) {
+            match self.inverted {
+                true => hc.set_high(pin_index),
+                false => hc.set_low(pin_index),
+            }
+        }
+
+        // this is the number of bytes needed to hold the color data for the whole strip:
+        pub const fn byte_count(&self) -> usize {
+            self.led_count * self.color_order.bytes_per_led()
+        }
+
         pub fn send_bits<T>(
             &self,
             hc: &mut HardwareController<T>,
@@ -62,140 +251,2747 @@ pub mod ws28xx {
         ) where
             T: PeriodicTimer,
         {
+            self.send_bits_after_reset(hc, pin_index, bit_buffer, true, &self.strip_timings);
+        }
+
+        // this sends the bits, only holding the pin low for the reset time first if is_reset is
+        // true. Leaving out the reset lets a strip's bits be sent in several parts. The timings
+        // are passed in so a LogicalStrip can send the strip with its timings override:
+        fn send_bits_after_reset<T>(
+            &self,
+            hc: &mut HardwareController<T>,
+            pin_index: usize,
+            bit_buffer: impl IntoIterator<Item = bool>,
+            is_reset: bool,
+            timings: &StripTimings,
+        ) where
+            T: PeriodicTimer,
+        {
+            // work out how long the pin is high for each kind of bit:
+            let zero_high = timings.zero_symbol().high_ns;
+            let one_high = timings.one_symbol().high_ns;
+
             // restart the timer every time to make sure it's configured correctly and nobody has
-            // changed its interrupt timing settings:
-            hc.periodic_start((self.strip_timings.full_cycle / 3).nanoseconds());
+            // changed its interrupt timing settings. Each period is a whole bit, and the marks end
+            // the high time of a zero bit and a one bit:
+            hc.periodic_start(timings.full_cycle.nanoseconds());
+            hc.set_marks(zero_high.nanoseconds(), one_high.nanoseconds());
             // keep the data pin low long enough for the leds to reset
-            hc.set_low(pin_index);
-            for _ in 0..WS2811_DELAY_LOOPS_BEFORE_SEND {
-                hc.periodic_wait();
+            self.set_data_low(hc, pin_index);
+            if is_reset {
+                #[cfg(target_arch = "riscv32")]
+                let reset_start = mcycle::read();
+                for _ in 0..timings.reset_periods() {
+                    hc.periodic_wait();
+                }
+                #[cfg(target_arch = "riscv32")]
+                LAST_RESET_CYCLES
+                    .store(mcycle::read().wrapping_sub(reset_start), Ordering::Relaxed);
             }
             // iterate over the bits and send them to the pin with appropriate timing
             let mut bit_iter = bit_buffer.into_iter();
             let mut next_bit = bit_iter.next();
 
-            // load the next bit during the longest pause
             while let Some(bit) = next_bit {
-                hc.set_high(pin_index);
-                match bit {
-                    true => {
-                        // on for 2/3 of the total time:
-                        next_bit = bit_iter.next();
-                        while hc.periodic_check_timeout().is_err() {}
-                        hc.periodic_wait();
-                        hc.set_low(pin_index);
-                        hc.periodic_wait();
-                    }
-                    false => {
-                        // on for 1/3 of the total time:
-                        hc.periodic_wait();
-                        hc.set_low(pin_index);
-                        next_bit = bit_iter.next();
-                        while hc.periodic_check_timeout().is_err() {}
-                        hc.periodic_wait();
-                    }
+                let mark = match bit {
+                    true => TimerMark::Second,
+                    false => TimerMark::First,
+                };
+
+                self.set_data_high(hc, pin_index);
+                hc.wait_for_mark(mark);
+                self.set_data_low(hc, pin_index);
+
+                // load the next bit while the pin is low:
+                next_bit = bit_iter.next();
+                hc.periodic_wait();
+            }
+        }
+
+        // this changes the order the color channels are sent in at runtime. A LogicalStrip
+        // borrows its strips for as long as it lives, so the order can't be changed while one is
+        // using the strip: change it first, then make a new LogicalStrip from the same buffers.
+        // Strips in a const can be given their order with the builder instead. The reversal of
+        // the strip is applied separately, so it works the same with any color order:
+        pub fn set_color_order(&mut self, color_order: ColorOrder) {
+            self.color_order = color_order;
+        }
+
+        // this is the size of the SPI buffer needed by send_via_spi() for this strip:
+        pub const fn spi_byte_count(&self) -> usize {
+            self.byte_count() * SPI_BYTES_PER_BYTE
+        }
+
+        // this sends the bits the same way as send_bits(), but times them by busy-waiting on the
+        // mcycle CPU cycle counter instead of the periodic timer, so it doesn't need a timer at
+        // all and the timing doesn't depend on the timer's clock rate. sysclk_hz has to match the
+        // actual CPU clock frequency. Each bit is scheduled from the start of the previous bit,
+        // so any time spent loading the next bit doesn't add up over the frame. The cycle counter
+        // only exists on the chip, so this isn't available when building for the host:
+        #[cfg(target_arch = "riscv32")]
+        pub fn send_bits_cycle_timed<T>(
+            &self,
+            hc: &mut HardwareController<T>,
+            pin_index: usize,
+            bit_buffer: impl IntoIterator<Item = bool>,
+            sysclk_hz: u32,
+        ) {
+            let timings = &self.strip_timings;
+            let ns_to_cycles = |ns: u32| (ns as u64 * sysclk_hz as u64 / 1_000_000_000) as usize;
+            let zero_high_cycles = ns_to_cycles(timings.zero_h);
+            let one_high_cycles = ns_to_cycles(timings.one_h);
+            let full_cycle_cycles = ns_to_cycles(timings.full_cycle);
+            let reset_cycles = ns_to_cycles(timings.reset_us * 1000);
+
+            // keep the data pin low long enough for the leds to reset
+            self.set_data_low(hc, pin_index);
+            let mut bit_start = mcycle::read();
+            wait_cycles_since(bit_start, reset_cycles);
+            bit_start = bit_start.wrapping_add(reset_cycles);
+
+            let mut bit_iter = bit_buffer.into_iter();
+            let mut next_bit = bit_iter.next();
+
+            while let Some(bit) = next_bit {
+                let high_cycles = match bit {
+                    true => one_high_cycles,
+                    false => zero_high_cycles,
+                };
+
+                self.set_data_high(hc, pin_index);
+                wait_cycles_since(bit_start, high_cycles);
+                self.set_data_low(hc, pin_index);
+
+                // load the next bit while the pin is low:
+                next_bit = bit_iter.next();
+                wait_cycles_since(bit_start, full_cycle_cycles);
+                bit_start = bit_start.wrapping_add(full_cycle_cycles);
+            }
+        }
+
+        // this sends the strip's bytes out of an SPI peripheral instead of bit-banging a pin, so
+        // the timing is handled by the hardware and isn't affected by interrupts. The SPI must
+        // already be configured for SPI_BITS_PER_SECOND in mode 0, and the strip has to be wired
+        // to its MOSI pin rather than to an arbitrary GPIO pin. Each data bit is expanded into a
+        // 3 bit pattern in spi_buffer, which must be at least spi_byte_count() long. The strip's
+        // own timings and inversion are not used, since the bit shape is fixed by the SPI clock:
+        pub fn send_via_spi<S>(
+            &self,
+            spi: &mut S,
+            bytes: &[u8],
+            spi_buffer: &mut [u8],
+        ) -> Result<(), SpiSendError<S::Error>>
+        where
+            S: Write<u8>,
+        {
+            let needed = bytes.len() * SPI_BYTES_PER_BYTE;
+            let length = spi_buffer.len();
+            let spi_buffer = spi_buffer
+                .get_mut(..needed)
+                .ok_or(SpiSendError::BufferTooSmall { needed, length })?;
+
+            for (byte, spi_bytes) in bytes
+                .iter()
+                .zip(spi_buffer.chunks_exact_mut(SPI_BYTES_PER_BYTE))
+            {
+                // a 0 bit is sent as 0b100 and a 1 bit as 0b110, most significant bit first:
+                let mut pattern: u32 = 0;
+                for bit_index in (0..8).rev() {
+                    let bit = (byte >> bit_index) & 1 != 0;
+                    pattern = (pattern << 3) | if bit { 0b110 } else { 0b100 };
+                }
+                spi_bytes.copy_from_slice(&pattern.to_be_bytes()[1..]);
+            }
+
+            spi.write(&[0; SPI_RESET_BYTE_COUNT])
+                .map_err(SpiSendError::Spi)?;
+            spi.write(spi_buffer).map_err(SpiSendError::Spi)
+        }
+    }
+
+    /// This builds a PhysicalStrip one setting at a time, starting from `PhysicalStrip::builder()`.
+    /// Any settings that aren't given keep their defaults: not reversed, GRB color order, WS2812
+    /// timings, full brightness and not inverted.
+    pub struct PhysicalStripBuilder {
+        strip: PhysicalStrip,
+    }
+
+    impl PhysicalStripBuilder {
+        pub const fn reversed(mut self, reversed: bool) -> Self {
+            self.strip.reversed = reversed;
+            self
+        }
+
+        pub const fn color_order(mut self, color_order: ColorOrder) -> Self {
+            self.strip.color_order = color_order;
+            self
+        }
+
+        pub const fn timings(mut self, strip_timings: StripTimings) -> Self {
+            self.strip.strip_timings = strip_timings;
+            self
+        }
+
+        pub const fn brightness(mut self, brightness: u8) -> Self {
+            self.strip.brightness = brightness;
+            self
+        }
+
+        pub const fn inverted(mut self, inverted: bool) -> Self {
+            self.strip.inverted = inverted;
+            self
+        }
+
+        pub const fn build(self) -> PhysicalStrip {
+            self.strip
+        }
+    }
+
+    // this busy-waits until cycles CPU cycles have passed since start, handling the counter
+    // wrapping around:
+    #[cfg(target_arch = "riscv32")]
+    fn wait_cycles_since(start: usize, cycles: usize) {
+        while mcycle::read().wrapping_sub(start) < cycles {}
+    }
+
+    // this future is pending the first time it is polled, waking itself straight away so the
+    // executor polls it again after running any other tasks that are ready:
+    struct YieldNow {
+        is_yielded: bool,
+    }
+
+    impl Future for YieldNow {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            match self.is_yielded {
+                true => Poll::Ready(()),
+                false => {
+                    self.is_yielded = true;
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
                 }
             }
         }
     }
 
+    /// This keeps track of how far through a frame `LogicalStrip::send_step()` has got, so the
+    /// frame can be sent a few bits at a time. A new state starts at the beginning of a frame.
+    #[derive(Default)]
+    pub struct SendState {
+        strip_index: usize,
+        start_byte_index: usize,
+        bit_index: usize,
+    }
+
+    impl SendState {
+        pub fn new() -> Self {
+            SendState::default()
+        }
+
+        // this is true if nothing has been sent for the current frame yet:
+        pub fn is_at_frame_start(&self) -> bool {
+            self.strip_index == 0 && self.bit_index == 0
+        }
+    }
+
+    /// This is returned by `LogicalStrip::send_all_sequential()` to say how much was sent and how
+    /// long it took, including converting the colors and the reset time before each strip. The
+    /// cycles are read from mcycle, so they wrap after about a minute at full clock speed. There is
+    /// no cycle counter when built for the host, so they are always 0 there.
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct SendStats {
+        pub bits_sent: usize,
+        pub cycles: usize,
+    }
+
+    /// This keeps track of how far through sending a single strip `LogicalStrip::poll_send()` has
+    /// got. It is made by `LogicalStrip::begin_send()`.
+    pub struct StripSend {
+        strip_index: usize,
+        start_byte_index: usize,
+        bit_index: usize,
+    }
+
+    impl StripSend {
+        pub fn strip_index(&self) -> usize {
+            self.strip_index
+        }
+    }
+
+    /// This is returned by `LogicalStrip::send_step()` to say whether the frame is finished.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum SendProgress {
+        InProgress,
+        Complete,
+        /// Only returned by `LogicalStrip::send_all_abortable()`.
+        Aborted,
+    }
+
+    /// The order gamma correction and brightness scaling are applied in when colors are sent. The
+    /// color correction is always applied together with the brightness. Gamma then brightness is
+    /// the default, and keeps the brightness steps linear. Brightness then gamma dims more
+    /// smoothly at the low end, since the gamma curve is applied to the dimmed colors.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum CorrectionOrder {
+        GammaThenBrightness,
+        BrightnessThenGamma,
+    }
+
+    /// This is returned when the physical strips of a LogicalStrip don't match its buffers or pins,
+    /// or a strip index is past the end of them.
+    #[derive(Debug)]
+    pub enum StripConfigError {
+        /// There is no physical strip at this index.
+        NoSuchStrip { index: usize, strip_count: usize },
+        /// The byte buffer is too small to hold the bytes for all the strips.
+        BufferTooSmall { needed: usize, length: usize },
+        /// The color buffer length doesn't match the total number of leds in all the strips.
+        LedCountMismatch { led_count: usize, length: usize },
+        /// The HardwareController has fewer pins than there are strips with leds.
+        NotEnoughPins { needed: usize, pin_count: usize },
+    }
+
+    // this checks that there are enough pins for every strip with leds. Zero length strips after
+    // the last strip with leds are never sent, so they don't need a pin:
+    pub(crate) fn check_pin_count(
+        strips: &[PhysicalStrip],
+        pin_count: usize,
+    ) -> Result<(), StripConfigError> {
+        let needed = strips
+            .iter()
+            .rposition(|s| s.led_count > 0)
+            .map_or(0, |index| index + 1);
+        match needed > pin_count {
+            true => Err(StripConfigError::NotEnoughPins { needed, pin_count }),
+            false => Ok(()),
+        }
+    }
+
+    /// A named run of leds in a LogicalStrip, starting at start and len leds long. Zones can match
+    /// the physical strips, or be any other part of the LogicalStrip, like the top of a window.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct Zone {
+        pub start: usize,
+        pub len: usize,
+    }
+
+    impl Zone {
+        pub const fn new(start: usize, len: usize) -> Self {
+            Zone { start, len }
+        }
+
+        // this is the index one past the last led of the zone:
+        pub const fn end(&self) -> usize {
+            self.start + self.len
+        }
+    }
+
+    /// This is returned when trying to access an LED index past the end of a LogicalStrip.
+    #[derive(Debug)]
+    pub struct IndexError {
+        pub index: usize,
+        pub length: usize,
+    }
+
+    /// `get_total_num_leds()`, `get_total_num_bytes()` and `get_max_strip_num_bytes()` calculate
+    /// the buffer sizes for a const array of strips.
+    /// All the color setters change `color_buffer`. If the strip is double buffered, the colors
+    /// sent to the strips are read from `front_buffer` instead, so they only change when
+    /// `swap_buffers()` is called.
     pub struct LogicalStrip<'a> {
-        _byte_buffer: &'a mut [u8],
+        byte_buffer: &'a mut [u8],
         color_buffer: &'a mut [c::Color],
+        front_buffer: Option<&'a mut [c::Color]>,
         strips: &'a [PhysicalStrip],
+        is_gamma_corrected: bool,
+        brightness: u8,
+        color_correction: c::Color,
+        correction_order: CorrectionOrder,
+        dither_buffer: Option<&'a mut [[u8; 4]]>,
+        is_dirty: bool,
+        // this counts how many times the colors have been converted to bytes, so tests can check
+        // that unchanged colors aren't converted again:
+        #[cfg(test)]
+        encode_count: usize,
+        is_white_extracted: bool,
+        is_flipped: bool,
+        power_limit: Option<PowerLimit>,
+        timings_override: Option<StripTimings>,
+    }
+
+    // this is the most current the strips are allowed to draw, and how much each channel of a led
+    // draws at full brightness:
+    struct PowerLimit {
+        limit_ma: u32,
+        per_channel_full_ma: u8,
     }
 
     impl<'a> LogicalStrip<'a> {
+        // this panics straight away if either buffer is too small for the strips, rather than
+        // running past the end of it the first time the strips are sent. Use try_new() to get an
+        // error instead:
         pub fn new(
             byte_buffer: &'a mut [u8],
             color_buffer: &'a mut [c::Color],
             strips: &'a [PhysicalStrip],
         ) -> Self {
-            LogicalStrip { color_buffer, strips, _byte_buffer: byte_buffer }
+            let led_count: usize = strips.iter().map(|s| s.led_count).sum();
+            assert!(
+                led_count <= color_buffer.len(),
+                "color buffer holds {} colors but the strips have {} leds",
+                color_buffer.len(),
+                led_count
+            );
+            let needed: usize = strips.iter().map(|s| s.byte_count()).sum();
+            assert!(
+                needed <= byte_buffer.len(),
+                "byte buffer holds {} bytes but the strips need {}",
+                byte_buffer.len(),
+                needed
+            );
+
+            LogicalStrip {
+                byte_buffer,
+                color_buffer,
+                front_buffer: None,
+                strips,
+                is_gamma_corrected: c::IS_GAMMA_CORRECTION_ENABLED,
+                brightness: u8::MAX,
+                color_correction: c::C_WHITE,
+                correction_order: CorrectionOrder::GammaThenBrightness,
+                dither_buffer: None,
+                is_dirty: true,
+                #[cfg(test)]
+                encode_count: 0,
+                is_white_extracted: false,
+                is_flipped: false,
+                power_limit: None,
+                timings_override: None,
+            }
+        }
+
+        // this works the same as new(), but checks that the color buffer has exactly one color
+        // for every led of the strips, and that the byte buffer is big enough for all their bytes.
+        // Each strip is always sent on the pin with the same index, so strips can't share pins:
+        pub fn try_new(
+            byte_buffer: &'a mut [u8],
+            color_buffer: &'a mut [c::Color],
+            strips: &'a [PhysicalStrip],
+        ) -> Result<Self, StripConfigError> {
+            let led_count = strips.iter().map(|s| s.led_count).sum();
+            if led_count != color_buffer.len() {
+                return Err(StripConfigError::LedCountMismatch {
+                    led_count,
+                    length: color_buffer.len(),
+                });
+            }
+
+            let needed = strips.iter().map(|s| s.byte_count()).sum();
+            if needed > byte_buffer.len() {
+                return Err(StripConfigError::BufferTooSmall { needed, length: byte_buffer.len() });
+            }
+
+            Ok(LogicalStrip::new(byte_buffer, color_buffer, strips))
+        }
+
+        // this works the same as try_new(), but the buffers only need to be at least big enough
+        // for the strips, and the color buffer is cut down to the strips' total led count. This
+        // lets the strips be changed at runtime, for example by keeping them in an ArrayVec and
+        // buffers sized for the most leds that will ever be used, and then making a new
+        // LogicalStrip from them whenever a strip is added, removed or changes length:
+        pub fn try_new_with_capacity(
+            byte_buffer: &'a mut [u8],
+            color_buffer: &'a mut [c::Color],
+            strips: &'a [PhysicalStrip],
+        ) -> Result<Self, StripConfigError> {
+            let led_count = strips.iter().map(|s| s.led_count).sum();
+            let length = color_buffer.len();
+            let color_buffer = color_buffer
+                .get_mut(..led_count)
+                .ok_or(StripConfigError::LedCountMismatch { led_count, length })?;
+            LogicalStrip::try_new(byte_buffer, color_buffer, strips)
+        }
+
+        // this checks that the HardwareController has a pin for every strip with leds. Strips are
+        // sent on the pin with the same index, so two strips can never share a pin, and sending a
+        // strip past the end of the pins panics:
+        pub fn check_pins<T>(&self, hc: &HardwareController<T>) -> Result<(), StripConfigError> {
+            check_pin_count(self.strips, hc.pin_count())
+        }
+
+        // this makes a strip that is drawn into the back_buffer while the front_buffer is being
+        // sent, so a frame that is only partly drawn is never sent out. Both buffers need to be
+        // the same length, since they are swapped, and this panics straight away if they aren't:
+        pub fn new_double_buffered(
+            byte_buffer: &'a mut [u8],
+            front_buffer: &'a mut [c::Color],
+            back_buffer: &'a mut [c::Color],
+            strips: &'a [PhysicalStrip],
+        ) -> Self {
+            assert_eq!(
+                front_buffer.len(),
+                back_buffer.len(),
+                "front and back buffers have different lengths"
+            );
+            let mut logical_strip = LogicalStrip::new(byte_buffer, back_buffer, strips);
+            logical_strip.front_buffer = Some(front_buffer);
+            logical_strip
+        }
+
+        // this swaps the front and back buffers of a double buffered strip, so the colors drawn
+        // since the last swap are sent from now on. Only the buffer references are swapped, so
+        // the new back buffer still holds the colors from before the last swap. This does nothing
+        // if the strip is not double buffered:
+        pub fn swap_buffers(&mut self) {
+            if let Some(front_buffer) = &mut self.front_buffer {
+                core::mem::swap(front_buffer, &mut self.color_buffer);
+                self.mark_dirty();
+            }
+        }
+
+        // this is true if the strip was made with new_double_buffered():
+        pub fn is_double_buffered(&self) -> bool {
+            self.front_buffer.is_some()
+        }
+
+        // this marks the bytes as out of date, so the colors are converted again before the next
+        // send. Every method that changes the colors or how they're converted has to call it:
+        fn mark_dirty(&mut self) {
+            self.is_dirty = true;
+        }
+
+        // this is the number of physical strips, including any zero length ones:
+        pub fn strip_count(&self) -> usize {
+            self.strips.len()
+        }
+
+        // this is the total number of leds across all the physical strips:
+        pub fn led_count(&self) -> usize {
+            self.color_buffer.len()
+        }
+
+        // this iterates over the colors of every led in order:
+        pub fn colors(&self) -> impl Iterator<Item = &c::Color> {
+            self.color_buffer.iter()
+        }
+
+        // this iterates over the colors of every led in order, allowing them to be changed:
+        pub fn colors_mut(&mut self) -> impl Iterator<Item = &mut c::Color> {
+            self.mark_dirty();
+            self.color_buffer.iter_mut()
         }
 
+        // this returns a copy of the color currently stored at index. It will panic if the index
+        // is out of bounds, use try_get_color_at_index() if the index might be too large:
         pub fn get_color_at_index(&self, index: usize) -> c::Color {
             self.color_buffer[index]
         }
 
-        // this sets the color value in the color array at index:
+        // this returns a copy of the color currently stored at index, or None if the index is
+        // past the end of the strip:
+        pub fn try_get_color_at_index(&self, index: usize) -> Option<c::Color> {
+            self.color_buffer.get(index).copied()
+        }
+
+        // this sets the color value in the color array at index. It will panic if the index is
+        // out of bounds, use try_set_color_at_index() if the index might be too large:
         pub fn set_color_at_index(&mut self, index: usize, color: c::Color) {
+            self.mark_dirty();
             self.color_buffer[index].set_color(color);
+        }
 
-            let mut index = index;
-            let (belongs_to, start) = self.belongs_to(index);
+        // this sets the color value in the color array at index, scaled by scale / 255 first so
+        // reading it back gives the dimmed color. It will panic if the index is out of bounds:
+        pub fn set_color_at_index_scaled(&mut self, index: usize, color: c::Color, scale: u8) {
+            self.set_color_at_index(index, color.scale(scale));
+        }
 
-            let [r, g, b] = belongs_to.color_order.offsets();
+        // this sets the color at index, wrapping around to the start of the strip for indices past
+        // the end, so ring effects don't have to wrap their positions themselves. Nothing is set
+        // if the strip has no leds:
+        pub fn set_color_at_index_wrapping(&mut self, index: usize, color: c::Color) {
+            if let Some(index) = index.checked_rem(self.color_buffer.len()) {
+                self.set_color_at_index(index, color);
+            }
+        }
 
-            let mut as_bytes = [0; 3];
+        // this works the same as set_color_at_index_wrapping(), but negative indices wrap around
+        // to the end of the strip, so -1 is the last led:
+        pub fn set_color_at_offset_wrapping(&mut self, offset: isize, color: c::Color) {
+            if let Some(index) = offset.checked_rem_euclid(self.color_buffer.len() as isize) {
+                self.set_color_at_index(index as usize, color);
+            }
+        }
 
-            as_bytes[r] = color.r;
-            as_bytes[g] = color.g;
-            as_bytes[b] = color.b;
+        // this sets the color at index and at the same distance from the other end of the strip,
+        // for strips that run out and back so patterns come out symmetric. On a strip with an odd
+        // number of leds the center led is its own mirror. It will panic if the index is out of
+        // bounds:
+        pub fn set_mirrored(&mut self, index: usize, color: c::Color) {
+            let mirror_index = self.color_buffer.len() - 1 - index;
+            self.set_color_at_index(index, color);
+            self.set_color_at_index(mirror_index, color);
+        }
 
-            if c::IS_GAMMA_CORRECTION_ENABLED {
-                as_bytes[r] = c::GAMMA8[color.r as usize];
-                as_bytes[g] = c::GAMMA8[color.g as usize];
-                as_bytes[b] = c::GAMMA8[color.b as usize];
+        // this sets the color value in the color array at index, or returns an error if the
+        // index is past the end of the strip:
+        pub fn try_set_color_at_index(
+            &mut self,
+            index: usize,
+            color: c::Color,
+        ) -> Result<(), IndexError> {
+            let length = self.color_buffer.len();
+            match self.color_buffer.get_mut(index) {
+                Some(led) => {
+                    led.set_color(color);
+                    self.mark_dirty();
+                    Ok(())
+                }
+                None => Err(IndexError { index, length }),
             }
+        }
 
-            if belongs_to.reversed {
-                let index_offset = index - start;
-                let reversed_index_offset = belongs_to.led_count - 1 - index_offset;
-                index = start + reversed_index_offset;
-            }
+        // this sends every strip with these timings instead of their own from the next frame on,
+        // so the timings can be tuned at runtime without reflashing, for example from a serial
+        // command. The strips themselves are left alone, and None goes back to their own timings:
+        pub fn set_timings_override(&mut self, strip_timings: Option<StripTimings>) {
+            self.timings_override = strip_timings;
+        }
 
-            for (i, _) in as_bytes.into_iter().enumerate() {
-                self._byte_buffer[(3 * index) + i] = as_bytes[i];
-            }
+        // this is the timings a strip is actually sent with, taking any override into account:
+        fn timings_for<'s>(&'s self, strip: &'s PhysicalStrip) -> &'s StripTimings {
+            self.timings_override
+                .as_ref()
+                .unwrap_or(&strip.strip_timings)
         }
 
-        fn belongs_to(&self, index: usize) -> (&PhysicalStrip, usize) {
-            let (mut start, mut end) = (0, 0);
+        // this turns gamma correction on or off for all colors sent to the strips from now on:
+        pub fn set_gamma_correction(&mut self, is_enabled: bool) {
+            self.mark_dirty();
+            self.is_gamma_corrected = is_enabled;
+        }
 
-            for strip in self.strips {
-                end += strip.led_count;
+        // this sets a master brightness that all colors are scaled by when they are sent to the
+        // strips. The color buffer keeps the full resolution colors, so changing the brightness
+        // repeatedly will not lose any color information:
+        pub fn set_brightness(&mut self, brightness: u8) {
+            self.mark_dirty();
+            self.brightness = brightness;
+        }
 
-                if index < end {
-                    return (strip, start);
-                };
+        // this sets the order gamma correction and brightness are applied in when colors are sent:
+        pub fn set_correction_order(&mut self, correction_order: CorrectionOrder) {
+            self.mark_dirty();
+            self.correction_order = correction_order;
+        }
+
+        // this turns on temporal dithering, which makes low brightness fades smoother by sending
+        // each channel rounded up on some frames and rounded down on others, so the average over
+        // a few frames is closer to the exact dimmed value. The buffer keeps the rounding error of
+        // every channel between frames, and needs an entry for every led. Dithering only looks
+        // smooth at a high frame rate, and leds past the end of the buffer aren't dithered.
+        // Passing None turns it off again:
+        pub fn set_dither_buffer(&mut self, dither_buffer: Option<&'a mut [[u8; 4]]>) {
+            self.mark_dirty();
+            self.dither_buffer = dither_buffer;
+        }
+
+        // this turns on moving the white part of each color onto the white led of RGBW strips,
+        // which uses less power and gives a cleaner white than mixing it from red, green and
+        // blue. RGB strips are not affected:
+        pub fn set_white_extraction(&mut self, is_enabled: bool) {
+            self.is_white_extracted = is_enabled;
+            self.mark_dirty();
+        }
+
+        // this estimates how much current the strips will draw in milliamps when the colors are
+        // sent, from how much each channel of a led draws when it is fully on. This is usually
+        // about 20mA for WS2812 leds. It goes by the bytes that are actually sent, so brightness,
+        // color correction, gamma correction and white extraction are all taken into account. The
+        // small current each led draws even when it's off isn't included:
+        pub fn estimate_current_ma(&self, per_channel_full_ma: u8) -> u32 {
+            let colors = match &self.front_buffer {
+                Some(front_buffer) => &front_buffer[..],
+                None => &self.color_buffer[..],
+            };
+            let gamma = |color: c::Color| match self.is_gamma_corrected {
+                true => color.gamma_correct(),
+                false => color,
+            };
+
+            let mut start_index = 0;
+            let mut total = 0_u64;
+            for strip in self.strips.iter() {
+                let end_index = start_index + strip.led_count;
+                let brightness = c::scale8(self.brightness, strip.brightness);
+                let dim = |color: c::Color| color.correct(self.color_correction).scale(brightness);
+                let is_rgbw = strip.color_order.bytes_per_led() == 4;
 
-                start = end;
+                for color in &colors[start_index..end_index] {
+                    let color = match self.is_white_extracted && is_rgbw {
+                        true => color.extract_white(),
+                        false => *color,
+                    };
+                    let color = match self.correction_order {
+                        CorrectionOrder::GammaThenBrightness => dim(gamma(color)),
+                        CorrectionOrder::BrightnessThenGamma => gamma(dim(color)),
+                    };
+                    // RGB strips don't have a white led, so the white channel draws nothing:
+                    let white = if is_rgbw { color.w as u64 } else { 0 };
+                    total += color.r as u64 + color.g as u64 + color.b as u64 + white;
+                }
+                start_index = end_index;
             }
-            panic!("Index out of bounds");
+            (total * per_channel_full_ma as u64 / 255) as u32
+        }
+
+        // this limits how much current the strips can draw, by dimming every led by the same
+        // amount whenever estimate_current_ma() is over limit_ma when the colors are sent. The
+        // colors themselves aren't changed. Passing None for limit_ma removes the limit:
+        pub fn set_power_limit_ma(&mut self, limit_ma: Option<u32>, per_channel_full_ma: u8) {
+            self.mark_dirty();
+            self.power_limit =
+                limit_ma.map(|limit_ma| PowerLimit { limit_ma, per_channel_full_ma });
+        }
+
+        // this sets a white balance correction that every color is multiplied by channel by channel
+        // when it is sent to the strips, e.g. (255, 176, 240) to warm up leds that look too blue.
+        // The default of (255, 255, 255) doesn't change the colors:
+        pub fn set_color_correction(&mut self, correction: c::Color) {
+            self.mark_dirty();
+            self.color_correction = correction;
         }
 
         // this fills the entire strip with a single color:
         pub fn set_strip_to_solid_color(&mut self, color: c::Color) {
+            self.mark_dirty();
             for c in &mut self.color_buffer.iter_mut() {
                 c.set_color(color);
             }
         }
 
-        // this will iterate over all the strips and send the led data in series:
-        pub fn send_all_sequential<T>(&self, hc: &mut HardwareController<T>)
+        // this turns every led off:
+        pub fn clear(&mut self) {
+            self.set_strip_to_solid_color(c::C_OFF);
+        }
+
+        // this turns every led off and sends the result to the strips straight away:
+        pub fn clear_and_send<T>(&mut self, hc: &mut HardwareController<T>)
         where
             T: PeriodicTimer,
         {
-            let mut start_index = 0;
+            self.clear();
+            self.swap_buffers();
+            self.send_all_sequential(hc);
+        }
 
-            for (pin_index, strip) in self.strips.iter().enumerate() {
-                let end_index = start_index + strip.led_count;
+        // this returns the zone covering the leds of the physical strip at strip_index, or None if
+        // there is no strip at that index:
+        pub fn physical_strip_zone(&self, strip_index: usize) -> Option<Zone> {
+            let strip = self.strips.get(strip_index)?;
+            let start = self.strips[..strip_index].iter().map(|s| s.led_count).sum();
+            Some(Zone::new(start, strip.led_count))
+        }
 
-                let start_byte_index = start_index * 3;
-                let end_byte_index = end_index * 3;
-                let bit_slice =
-                    Self::bytes_as_bit_slice(&self._byte_buffer[start_byte_index..end_byte_index]);
+        // this returns the colors of the leds in the zone so they can be changed directly, or an
+        // error if the zone goes past the end of the strip:
+        pub fn zone_colors_mut(&mut self, zone: Zone) -> Result<&mut [c::Color], IndexError> {
+            let length = self.color_buffer.len();
+            if self.color_buffer.get(zone.start..zone.end()).is_none() {
+                return Err(IndexError { index: zone.end(), length });
+            }
+            self.mark_dirty();
+            Ok(&mut self.color_buffer[zone.start..zone.end()])
+        }
 
-                strip.send_bits(hc, pin_index, bit_slice.iter().by_val());
+        // this fills the leds in the zone with a single color. If the zone goes past the end of
+        // the strip, no leds are changed:
+        pub fn fill_zone(&mut self, zone: Zone, color: c::Color) -> Result<(), IndexError> {
+            self.set_range_to_color(zone.start, zone.end(), color)
+        }
 
-                start_index = end_index;
+        // this fills only the leds of the physical strip at strip_index with a single color. The
+        // leds of a physical strip start after all the leds of the strips before it:
+        pub fn set_physical_strip_to_color(
+            &mut self,
+            strip_index: usize,
+            color: c::Color,
+        ) -> Result<(), StripConfigError> {
+            let strip_count = self.strips.len();
+            let strip = self
+                .strips
+                .get(strip_index)
+                .ok_or(StripConfigError::NoSuchStrip { index: strip_index, strip_count })?;
+            self.mark_dirty();
+
+            let start_index: usize = self.strips[..strip_index].iter().map(|s| s.led_count).sum();
+            let end_index = start_index + strip.led_count;
+            for led in &mut self.color_buffer[start_index..end_index] {
+                led.set_color(color);
             }
+            Ok(())
         }
 
-        // this takes an array of u8 color data and converts it into an array of bools
-        pub fn bytes_as_bit_slice(byte_buffer: &[u8]) -> &BitSlice<Msb0, u8> {
-            byte_buffer.view_bits::<Msb0>()
+        // this fills the leds from start up to but not including end with a single color. If the
+        // range is backwards or goes past the end of the strip, no leds are changed:
+        pub fn set_range_to_color(
+            &mut self,
+            start: usize,
+            end: usize,
+            color: c::Color,
+        ) -> Result<(), IndexError> {
+            let length = self.color_buffer.len();
+            match self.color_buffer.get_mut(start..end) {
+                Some(leds) => {
+                    leds.iter_mut().for_each(|led| led.set_color(color));
+                    self.mark_dirty();
+                    Ok(())
+                }
+                None => Err(IndexError { index: start.max(end), length }),
+            }
+        }
+
+        // this moves the color of every led by positions along the strip, towards the end of the
+        // strip for positive values and towards the start for negative ones. If wrap is true, the
+        // colors that move off one end come back on the other end, otherwise the leds left behind
+        // are turned off. Wrapping shifts larger than the strip wrap around more than once:
+        pub fn shift(&mut self, by: isize, wrap: bool) {
+            self.mark_dirty();
+            let led_count = self.color_buffer.len();
+            if led_count == 0 {
+                return;
+            }
+
+            let distance = match wrap {
+                true => by.unsigned_abs() % led_count,
+                false => by.unsigned_abs().min(led_count),
+            };
+            match by >= 0 {
+                true => self.color_buffer.rotate_right(distance),
+                false => self.color_buffer.rotate_left(distance),
+            }
+
+            if !wrap {
+                let vacated = match by >= 0 {
+                    true => 0..distance,
+                    false => led_count - distance..led_count,
+                };
+                for led in &mut self.color_buffer[vacated] {
+                    led.set_color(c::C_OFF);
+                }
+            }
+        }
+
+        // this moves the hue of every led around the color wheel by degrees:
+        pub fn rotate_hue(&mut self, degrees: u16) {
+            self.mark_dirty();
+            for led in self.color_buffer.iter_mut() {
+                *led = led.rotate_hue(degrees);
+            }
+        }
+
+        // this shifts the hue and scales the saturation and value of every led, see
+        // Color::adjust_hsv(). It can be used to warm up or desaturate whatever is being drawn:
+        pub fn adjust_hsv(&mut self, hue_shift: i16, sat_scale: u8, val_scale: u8) {
+            self.mark_dirty();
+            for led in self.color_buffer.iter_mut() {
+                *led = led.adjust_hsv(hue_shift, sat_scale, val_scale);
+            }
+        }
+
+        // this layers the other colors on top of the strip's current colors using the blend mode,
+        // starting at the first led. Extra colors past the end of the strip are ignored:
+        pub fn blend_from(&mut self, other: &[c::Color], mode: c::BlendMode) {
+            self.mark_dirty();
+            for (led, color) in self.color_buffer.iter_mut().zip(other) {
+                *led = led.blend(color, mode);
+            }
+        }
+
+        // this draws the overlay colors on top of the strip's current colors, each with its own
+        // alpha from 0 (not drawn) to 255 (fully covers the led), starting at the first led. Only
+        // as many leds as there are both overlay colors and alphas are changed:
+        pub fn composite(&mut self, overlay: &[c::Color], alpha: &[u8]) {
+            self.mark_dirty();
+            let layer = overlay.iter().zip(alpha);
+            for (led, (color, &alpha)) in self.color_buffer.iter_mut().zip(layer) {
+                *led = led.composite(color, alpha);
+            }
+        }
+
+        // this moves every channel of each led at most max_delta closer to the matching target
+        // color, starting at the first led. Calling it every frame smoothly fades the strip to
+        // the target, however the target was drawn. Extra target colors past the end of the strip
+        // are ignored:
+        pub fn step_toward_target(&mut self, target: &[c::Color], max_delta: u8) {
+            self.mark_dirty();
+            for (led, target) in self.color_buffer.iter_mut().zip(target) {
+                *led = led.step_toward(target, max_delta);
+            }
+        }
+
+        // this spreads the stop colors evenly over the whole strip, with the first stop on the
+        // first led and the last stop on the last led. The leds in between stops are linearly
+        // interpolated between them. If there are more stops than leds, the stops are sampled:
+        pub fn set_gradient(&mut self, stops: &[c::Color]) {
+            self.mark_dirty();
+            let led_count = self.color_buffer.len();
+            let stop_count = stops.len();
+
+            if stop_count == 0 || led_count == 0 {
+                return;
+            }
+            if stop_count == 1 || led_count == 1 {
+                self.set_strip_to_solid_color(stops[0]);
+                return;
+            }
+
+            // each led position is scaled so that there are (led_count - 1) steps between stops:
+            let steps_between_stops = led_count - 1;
+            for (index, led) in self.color_buffer.iter_mut().enumerate() {
+                let position = index * (stop_count - 1);
+                let stop_index = position / steps_between_stops;
+
+                if stop_index >= stop_count - 1 {
+                    led.set_color(stops[stop_count - 1]);
+                    continue;
+                }
+
+                let color = c::Color::color_lerp(
+                    (position % steps_between_stops) as i32,
+                    0,
+                    steps_between_stops as i32,
+                    stops[stop_index],
+                    stops[stop_index + 1],
+                );
+                led.set_color(color);
+            }
+        }
+
+        // this will iterate over all the strips and send the led data in series:
+        pub fn send_all_sequential<T>(&mut self, hc: &mut HardwareController<T>) -> SendStats
+        where
+            T: PeriodicTimer,
+        {
+            #[cfg(target_arch = "riscv32")]
+            let start = mcycle::read();
+            self.colors_to_bytes();
+
+            let mut start_byte_index = 0;
+            let mut bits_sent = 0;
+
+            for (pin_index, strip) in self.strips.iter().enumerate() {
+                // zero length strips are skipped so they don't add a reset delay:
+                if strip.led_count == 0 {
+                    continue;
+                }
+                let end_byte_index = start_byte_index + strip.byte_count();
+
+                let bit_slice =
+                    Self::bytes_as_bit_slice(&self.byte_buffer[start_byte_index..end_byte_index]);
+
+                #[cfg(feature = "defmt")]
+                defmt::trace!("sending {} bytes on pin {}", strip.byte_count(), pin_index);
+
+                let bits = bit_slice.iter().by_val();
+                strip.send_bits_after_reset(hc, pin_index, bits, true, self.timings_for(strip));
+
+                #[cfg(feature = "defmt")]
+                defmt::trace!("finished sending on pin {}", pin_index);
+
+                bits_sent += bit_slice.len();
+                start_byte_index = end_byte_index;
+            }
+
+            SendStats {
+                bits_sent,
+                #[cfg(target_arch = "riscv32")]
+                cycles: mcycle::read().wrapping_sub(start),
+                #[cfg(not(target_arch = "riscv32"))]
+                cycles: 0,
+            }
+        }
+
+        // this sends the strips the same way as send_all_sequential(), but with every strip in
+        // the opposite direction to its reversed setting, so effects can be flipped for a frame
+        // without changing the strips. The colors have to be converted again for both this send
+        // and the next normal one:
+        pub fn send_all_sequential_reversed<T>(
+            &mut self,
+            hc: &mut HardwareController<T>,
+        ) -> SendStats
+        where
+            T: PeriodicTimer,
+        {
+            self.is_flipped = true;
+            self.mark_dirty();
+            let stats = self.send_all_sequential(hc);
+            self.is_flipped = false;
+            self.mark_dirty();
+            stats
+        }
+
+        // this sends the frame like send_all_sequential(), but yields to the async executor after
+        // every max_bits bits so other tasks can run partway through a strip. The bits themselves
+        // are still sent blocking, since they are only a few hundred nanoseconds long, which is
+        // far less time than switching tasks takes. The pin stays low while other tasks run, so
+        // they have to hand back control within the strip's reset time or the leds will latch a
+        // partial frame. Passing usize::MAX only yields between strips, which is always safe,
+        // because every strip starts with its own reset before its first bit:
+        pub async fn send_all_async<T>(
+            &mut self,
+            hc: &mut HardwareController<'_, T>,
+            max_bits: usize,
+        ) where
+            T: PeriodicTimer,
+        {
+            let mut state = SendState::new();
+            while self.send_step(hc, &mut state, max_bits) == SendProgress::InProgress {
+                YieldNow { is_yielded: false }.await;
+            }
+        }
+
+        // this sends the strips one at a time like send_all_sequential(), but checks the abort flag
+        // before each strip and stops with Aborted if it is set, so a stale frame can be dropped
+        // as soon as new colors arrive. The flag can be set from an interrupt, and isn't cleared
+        // here. Strips are never stopped partway through, so all the pins are left low:
+        pub fn send_all_abortable<T>(
+            &mut self,
+            hc: &mut HardwareController<T>,
+            abort: &AtomicBool,
+        ) -> SendProgress
+        where
+            T: PeriodicTimer,
+        {
+            let mut state = SendState::new();
+            loop {
+                if abort.load(Ordering::Relaxed) {
+                    return SendProgress::Aborted;
+                }
+                if self.send_step(hc, &mut state, usize::MAX) == SendProgress::Complete {
+                    return SendProgress::Complete;
+                }
+            }
+        }
+
+        // this sends at most max_bits of the frame and then returns, so sending can be interleaved
+        // with other work. It keeps calling until it returns Complete, after which the state is
+        // back at the start of the next frame. The colors are only converted to bytes at the
+        // start of each frame, and the reset time is only sent at the start of each strip. The
+        // pin stays low between calls, so the time between calls for the same strip has to be
+        // shorter than its reset time or the leds will latch a partial frame:
+        pub fn send_step<T>(
+            &mut self,
+            hc: &mut HardwareController<T>,
+            state: &mut SendState,
+            max_bits: usize,
+        ) -> SendProgress
+        where
+            T: PeriodicTimer,
+        {
+            if state.is_at_frame_start() {
+                self.colors_to_bytes();
+            }
+            self.skip_empty_strips(state);
+
+            let strip = match self.strips.get(state.strip_index) {
+                Some(strip) => strip,
+                None => {
+                    *state = SendState::new();
+                    return SendProgress::Complete;
+                }
+            };
+
+            let end_byte_index = state.start_byte_index + strip.byte_count();
+            let bit_slice =
+                Self::bytes_as_bit_slice(&self.byte_buffer[state.start_byte_index..end_byte_index]);
+            let end_bit_index = state
+                .bit_index
+                .saturating_add(max_bits.max(1))
+                .min(bit_slice.len());
+            let bit_count = end_bit_index - state.bit_index;
+            let bits = bit_slice
+                .iter()
+                .by_val()
+                .skip(state.bit_index)
+                .take(bit_count);
+            let is_reset = state.bit_index == 0;
+
+            let timings = self.timings_for(strip);
+            strip.send_bits_after_reset(hc, state.strip_index, bits, is_reset, timings);
+
+            state.bit_index = end_bit_index;
+            if state.bit_index == bit_slice.len() {
+                state.strip_index += 1;
+                state.start_byte_index = end_byte_index;
+                state.bit_index = 0;
+                self.skip_empty_strips(state);
+            }
+
+            match state.strip_index < self.strips.len() {
+                true => SendProgress::InProgress,
+                false => {
+                    *state = SendState::new();
+                    SendProgress::Complete
+                }
+            }
+        }
+
+        // this sends only the strip at strip_index, so a change to one strip can be shown without
+        // taking the time to send all the others. The other strips latch their colors, so they
+        // keep showing whatever they were last sent:
+        pub fn send_strip<T>(
+            &mut self,
+            hc: &mut HardwareController<T>,
+            strip_index: usize,
+        ) -> Result<(), StripConfigError>
+        where
+            T: PeriodicTimer,
+        {
+            let mut send = self.begin_send(strip_index)?;
+            self.poll_send(hc, &mut send, usize::MAX);
+            Ok(())
+        }
+
+        // this starts sending a single strip, converting the colors to bytes if they have changed.
+        // Nothing is sent until poll_send() is called, so sends of several strips can be started
+        // and then polled in turn to interleave them:
+        pub fn begin_send(&mut self, strip_index: usize) -> Result<StripSend, StripConfigError> {
+            let strip_count = self.strips.len();
+            if strip_index >= strip_count {
+                return Err(StripConfigError::NoSuchStrip { index: strip_index, strip_count });
+            }
+            self.colors_to_bytes();
+
+            let start_byte_index = self.strips[..strip_index]
+                .iter()
+                .map(|s| s.byte_count())
+                .sum();
+            Ok(StripSend { strip_index, start_byte_index, bit_index: 0 })
+        }
+
+        // this sends at most max_bits more of a strip started with begin_send(), and returns true
+        // once the whole strip has been sent. The reset is sent on the first call. The pin stays
+        // low between calls, so the time between calls for the same strip has to be shorter than
+        // its reset time or the leds will latch a partial frame:
+        pub fn poll_send<T>(
+            &mut self,
+            hc: &mut HardwareController<T>,
+            send: &mut StripSend,
+            max_bits: usize,
+        ) -> bool
+        where
+            T: PeriodicTimer,
+        {
+            let strip = &self.strips[send.strip_index];
+            let end_byte_index = send.start_byte_index + strip.byte_count();
+            let bit_slice =
+                Self::bytes_as_bit_slice(&self.byte_buffer[send.start_byte_index..end_byte_index]);
+            if send.bit_index >= bit_slice.len() {
+                return true;
+            }
+
+            let end_bit_index = send
+                .bit_index
+                .saturating_add(max_bits.max(1))
+                .min(bit_slice.len());
+            let bits = bit_slice
+                .iter()
+                .by_val()
+                .skip(send.bit_index)
+                .take(end_bit_index - send.bit_index);
+            let is_reset = send.bit_index == 0;
+
+            let timings = self.timings_for(strip);
+            strip.send_bits_after_reset(hc, send.strip_index, bits, is_reset, timings);
+
+            send.bit_index = end_bit_index;
+            send.bit_index == bit_slice.len()
+        }
+
+        // this does all the work of sending a frame except for touching the pins and waiting on
+        // the timer, so the cost of encoding a frame can be measured separately from the time it
+        // takes to send it. It returns the number of 1 bits in the frame, so the bit iteration
+        // can't be optimized away:
+        pub fn render_only(&mut self) -> usize {
+            self.colors_to_bytes();
+
+            let mut start_byte_index = 0;
+            let mut one_bit_count = 0;
+            for strip in self.strips.iter().filter(|s| s.led_count > 0) {
+                let end_byte_index = start_byte_index + strip.byte_count();
+                let bit_slice =
+                    Self::bytes_as_bit_slice(&self.byte_buffer[start_byte_index..end_byte_index]);
+                one_bit_count += bit_slice.iter().by_val().filter(|&bit| bit).count();
+                start_byte_index = end_byte_index;
+            }
+            one_bit_count
+        }
+
+        // this is the bytes for all the strips as they were last sent or rendered:
+        pub fn bytes(&self) -> &[u8] {
+            &self.byte_buffer[..]
+        }
+
+        // this encodes the current colors and returns the bytes that would be sent to the strip
+        // at strip_index, with its color order, reversal, gamma correction and brightness applied.
+        // This lets the bytes be checked, or sent some other way like over SPI or a network:
+        pub fn strip_bytes(&mut self, strip_index: usize) -> Result<&[u8], StripConfigError> {
+            let strip_count = self.strips.len();
+            if strip_index >= strip_count {
+                return Err(StripConfigError::NoSuchStrip { index: strip_index, strip_count });
+            }
+            self.colors_to_bytes();
+
+            let start_byte_index: usize = self.strips[..strip_index]
+                .iter()
+                .map(|s| s.byte_count())
+                .sum();
+            let end_byte_index = start_byte_index + self.strips[strip_index].byte_count();
+            Ok(&self.byte_buffer[start_byte_index..end_byte_index])
+        }
+
+        // this moves the send state past any zero length strips, so they are skipped entirely:
+        fn skip_empty_strips(&self, state: &mut SendState) {
+            while self
+                .strips
+                .get(state.strip_index)
+                .is_some_and(|s| s.led_count == 0)
+            {
+                state.strip_index += 1;
+            }
+        }
+
+        // this will send the led data to all the strips at the same time, so a frame only takes as
+        // long as the longest strip. All strips are sent using the timings of the first strip with
+        // any leds, zero length strips are skipped, and the pins of strips that run out of data
+        // are held low while the longer strips finish:
+        pub fn send_all_parallel<T>(&mut self, hc: &mut HardwareController<T>)
+        where
+            T: PeriodicTimer,
+        {
+            self.colors_to_bytes();
+
+            let timings = match self.strips.iter().find(|s| s.led_count > 0) {
+                Some(strip) => self.timings_for(strip),
+                None => return,
+            };
+            let zero_high = timings.zero_symbol().high_ns;
+            let one_high = timings.one_symbol().high_ns;
+
+            let max_bit_count = self
+                .strips
+                .iter()
+                .map(|s| s.byte_count() * 8)
+                .max()
+                .unwrap_or(0);
+
+            // keep all the data pins low long enough for the strip with the longest reset time:
+            let reset_periods = self
+                .strips
+                .iter()
+                .filter(|s| s.led_count > 0)
+                .map(|s| self.timings_for(s).reset_periods_at(timings.full_cycle))
+                .max()
+                .unwrap_or(0);
+
+            hc.periodic_start(timings.full_cycle.nanoseconds());
+            hc.set_marks(zero_high.nanoseconds(), one_high.nanoseconds());
+            for (pin_index, strip) in self.strips.iter().enumerate() {
+                if strip.led_count > 0 {
+                    strip.set_data_low(hc, pin_index);
+                }
+            }
+            for _ in 0..reset_periods {
+                hc.periodic_wait();
+            }
+
+            #[cfg(feature = "defmt")]
+            defmt::trace!("sending {} bits in parallel", max_bit_count);
+
+            for bit_index in 0..max_bit_count {
+                // every strip that still has data starts its bit at the same time:
+                for (pin_index, strip) in self.strips.iter().enumerate() {
+                    if bit_index < strip.byte_count() * 8 {
+                        strip.set_data_high(hc, pin_index);
+                    }
+                }
+
+                // then the pins sending a zero are set low at the first mark, and the pins
+                // sending a one at the second:
+                for (mark, is_one) in [(TimerMark::First, false), (TimerMark::Second, true)] {
+                    hc.wait_for_mark(mark);
+
+                    let mut start_byte_index = 0;
+                    for (pin_index, strip) in self.strips.iter().enumerate() {
+                        if bit_index < strip.byte_count() * 8 {
+                            let byte = self.byte_buffer[start_byte_index + bit_index / 8];
+                            if (byte & (0x80 >> (bit_index % 8)) != 0) == is_one {
+                                strip.set_data_low(hc, pin_index);
+                            }
+                        }
+                        start_byte_index += strip.byte_count();
+                    }
+                }
+                hc.periodic_wait();
+            }
+
+            #[cfg(feature = "defmt")]
+            defmt::trace!("finished sending in parallel");
+        }
+
+        // this converts the color buffer into the byte buffer that is sent out to the strips,
+        // applying the color order, reversal, gamma correction and brightness of each strip.
+        // Every method that changes the colors or how they're converted marks the bytes as dirty,
+        // and they are only converted again after that:
+        fn colors_to_bytes(&mut self) {
+            // the bytes from last time can be sent again if nothing has changed since then, unless
+            // dithering is on, since that changes the bytes every frame:
+            if !self.is_dirty && self.dither_buffer.is_none() {
+                return;
+            }
+            self.is_dirty = false;
+            #[cfg(test)]
+            {
+                self.encode_count += 1;
+            }
+            let (mut start_index, mut start_byte_index) = (0, 0);
+
+            // dim everything in proportion if the strips would draw more than the power limit:
+            let power_scale = match &self.power_limit {
+                Some(power_limit) => {
+                    let estimate_ma = self.estimate_current_ma(power_limit.per_channel_full_ma);
+                    match estimate_ma > power_limit.limit_ma {
+                        // one lower, so rounding in scale8() can't take it over the limit:
+                        true => {
+                            let scale = power_limit.limit_ma as u64 * 255 / estimate_ma as u64;
+                            (scale as u8).saturating_sub(1)
+                        }
+                        false => u8::MAX,
+                    }
+                }
+                None => u8::MAX,
+            };
+
+            for strip in self.strips.iter() {
+                let end_index = start_index + strip.led_count;
+                let bytes_per_led = strip.color_order.bytes_per_led();
+                let offsets = strip.color_order.offsets();
+
+                // the strip's own brightness is scaled by the global brightness:
+                let brightness = c::scale8(self.brightness, strip.brightness);
+                let brightness = c::scale8(brightness, power_scale);
+
+                let colors = match &self.front_buffer {
+                    Some(front_buffer) => front_buffer[start_index..end_index].iter(),
+                    None => self.color_buffer[start_index..end_index].iter(),
+                };
+                for (index_offset, color) in colors.enumerate() {
+                    let gamma = |color: c::Color| match self.is_gamma_corrected {
+                        true => color.gamma_correct(),
+                        false => color,
+                    };
+                    let dither_error = self
+                        .dither_buffer
+                        .as_deref_mut()
+                        .and_then(|buffer| buffer.get_mut(start_index + index_offset));
+                    let dim = |color: c::Color| match dither_error {
+                        Some(error) => {
+                            color.scale_dithered(self.color_correction, brightness, error)
+                        }
+                        None => color.correct(self.color_correction).scale(brightness),
+                    };
+                    let color = match self.is_white_extracted && bytes_per_led == 4 {
+                        true => color.extract_white(),
+                        false => *color,
+                    };
+                    let color = match self.correction_order {
+                        CorrectionOrder::GammaThenBrightness => dim(gamma(color)),
+                        CorrectionOrder::BrightnessThenGamma => gamma(dim(color)),
+                    };
+
+                    // flipping a send reverses every strip from how it is normally sent:
+                    let index_offset = match strip.reversed != self.is_flipped {
+                        true => strip.led_count - 1 - index_offset,
+                        false => index_offset,
+                    };
+
+                    let byte_index = start_byte_index + index_offset * bytes_per_led;
+                    let as_bytes = &mut self.byte_buffer[byte_index..byte_index + bytes_per_led];
+
+                    // RGB strips only have 3 offsets, so the white channel is skipped for them:
+                    let channels = [color.r, color.g, color.b, color.w];
+                    for (&channel, offset) in channels.iter().zip(offsets).take(bytes_per_led) {
+                        as_bytes[offset] = channel;
+                    }
+                }
+
+                start_index = end_index;
+                start_byte_index += strip.byte_count();
+            }
+        }
+
+        // this sends a single strip out of an SPI peripheral, see PhysicalStrip::send_via_spi()
+        // for how the SPI has to be set up. Only one strip can be connected to each SPI MOSI pin,
+        // so strip_index picks which strip's data is sent. Indices past the last strip and
+        // zero length strips are ignored:
+        pub fn send_strip_via_spi<S>(
+            &mut self,
+            strip_index: usize,
+            spi: &mut S,
+            spi_buffer: &mut [u8],
+        ) -> Result<(), SpiSendError<S::Error>>
+        where
+            S: Write<u8>,
+        {
+            self.colors_to_bytes();
+
+            let start_byte_index: usize = self
+                .strips
+                .iter()
+                .take(strip_index)
+                .map(|s| s.byte_count())
+                .sum();
+
+            match self.strips.get(strip_index) {
+                Some(strip) if strip.led_count > 0 => {
+                    let end_byte_index = start_byte_index + strip.byte_count();
+                    let bytes = &self.byte_buffer[start_byte_index..end_byte_index];
+                    strip.send_via_spi(spi, bytes, spi_buffer)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        // this takes an array of u8 color data and converts it into an array of bools
+        pub fn bytes_as_bit_slice(byte_buffer: &[u8]) -> &BitSlice<Msb0, u8> {
+            byte_buffer.view_bits::<Msb0>()
+        }
+    }
+
+    impl<'s, 'a> IntoIterator for &'s LogicalStrip<'a> {
+        type Item = &'s c::Color;
+        type IntoIter = core::slice::Iter<'s, c::Color>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.color_buffer.iter()
+        }
+    }
+
+    impl<'s, 'a> IntoIterator for &'s mut LogicalStrip<'a> {
+        type Item = &'s mut c::Color;
+        type IntoIter = core::slice::IterMut<'s, c::Color>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.mark_dirty();
+            self.color_buffer.iter_mut()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        #[cfg(feature = "std")]
+        use crate::{
+            hardware::DynamicPin,
+            mock::{MockClock, MockPinControl, MockTimer},
+        };
+        use core::ops::RangeInclusive;
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn send_all_sequential_counts_every_bit() {
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(3).build(),
+                PhysicalStrip::builder(2).build(),
+            ];
+            const NUM_LEDS: usize = crate::get_total_num_leds(&STRIPS);
+            let mut bytes = [0; crate::get_total_num_bytes(&STRIPS)];
+            let mut colors = [c::C_RED; NUM_LEDS];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let (mut pin_0, mut pin_1) =
+                (MockPinControl::new(0, &clock), MockPinControl::new(1, &clock));
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+
+            let stats = strip.send_all_sequential(&mut hc);
+            assert_eq!(stats.bits_sent, NUM_LEDS * 24);
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn zero_length_strips_are_not_sent() {
+            const STRIPS: [PhysicalStrip; 3] = [
+                PhysicalStrip::builder(1).build(),
+                PhysicalStrip::builder(0).build(),
+                PhysicalStrip::builder(1).build(),
+            ];
+            let mut bytes = [0; 6];
+            let mut colors = [c::C_RED, c::C_BLUE];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let mut pin_0 = MockPinControl::new(0, &clock);
+            let mut pin_1 = MockPinControl::new(1, &clock);
+            let mut pin_2 = MockPinControl::new(2, &clock);
+            let mut pins: [DynamicPin; 3] = [&mut pin_0, &mut pin_1, &mut pin_2];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+
+            strip.send_all_sequential(&mut hc);
+            assert!(clock.transitions().iter().all(|t| t.pin != 1));
+            let timings = StripTimings::WS2812_ADAFRUIT;
+            assert_eq!(clock.decode_bytes(0, &timings), [0, 255, 0]);
+            assert_eq!(clock.decode_bytes(2, &timings), [0, 0, 255]);
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn changing_the_color_order_swaps_the_sent_bytes() {
+            let mut strips = [PhysicalStrip::builder(2).reversed(true).build()];
+            let mut bytes = [0; 6];
+            let mut colors = [c::C_RED, c::C_BLUE];
+            let timings = StripTimings::WS2812_ADAFRUIT;
+
+            let clock = MockClock::new();
+            let mut pin = MockPinControl::new(0, &clock);
+            let mut pins: [DynamicPin; 1] = [&mut pin];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+
+            LogicalStrip::new(&mut bytes, &mut colors, &strips).send_all_sequential(&mut hc);
+            assert_eq!(clock.decode_bytes(0, &timings), [0, 0, 255, 0, 255, 0]);
+
+            clock.clear();
+            strips[0].set_color_order(ColorOrder::RGB);
+            LogicalStrip::new(&mut bytes, &mut colors, &strips).send_all_sequential(&mut hc);
+            assert_eq!(clock.decode_bytes(0, &timings), [0, 0, 255, 255, 0, 0]);
+        }
+
+        // this is the longest time the pin was held high for, which is the high time of a 1 bit:
+        #[cfg(feature = "std")]
+        fn longest_high_ns(clock: &MockClock) -> u64 {
+            clock
+                .transitions()
+                .windows(2)
+                .filter(|pair| pair[0].is_high && !pair[1].is_high)
+                .map(|pair| pair[1].time_ns - pair[0].time_ns)
+                .max()
+                .unwrap_or(0)
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn timings_override_changes_the_sent_timings() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1).build()];
+            let mut bytes = [0; 3];
+            let mut colors = [c::C_WHITE; 1];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let mut pin = MockPinControl::new(0, &clock);
+            let mut pins: [DynamicPin; 1] = [&mut pin];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+
+            strip.send_all_sequential(&mut hc);
+            let strip_high_ns = longest_high_ns(&clock);
+
+            clock.clear();
+            strip.set_timings_override(Some(StripTimings::WS2811_ADAFRUIT));
+            strip.send_all_sequential(&mut hc);
+            assert!(longest_high_ns(&clock) > strip_high_ns);
+
+            clock.clear();
+            strip.set_timings_override(None);
+            strip.send_all_sequential(&mut hc);
+            assert_eq!(longest_high_ns(&clock), strip_high_ns);
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn drawing_mid_send_only_changes_the_back_buffer() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(2).build()];
+            let mut bytes = [0; 6];
+            let (mut front, mut back) = ([c::C_OFF; 2], [c::C_OFF; 2]);
+            let mut strip =
+                LogicalStrip::new_double_buffered(&mut bytes, &mut front, &mut back, &STRIPS);
+            strip.set_gamma_correction(false);
+            strip.set_strip_to_solid_color(c::C_RED);
+            strip.swap_buffers();
+
+            let clock = MockClock::new();
+            let mut pin = MockPinControl::new(0, &clock);
+            let mut pins: [DynamicPin; 1] = [&mut pin];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+
+            let mut state = SendState::new();
+            assert_eq!(strip.send_step(&mut hc, &mut state, 12), SendProgress::InProgress);
+            strip.set_strip_to_solid_color(c::C_BLUE);
+            while strip.send_step(&mut hc, &mut state, 12) == SendProgress::InProgress {}
+
+            let timings = StripTimings::WS2812_ADAFRUIT;
+            assert_eq!(clock.decode_bytes(0, &timings), [0, 255, 0, 0, 255, 0]);
+
+            clock.clear();
+            strip.swap_buffers();
+            strip.send_all_sequential(&mut hc);
+            assert_eq!(clock.decode_bytes(0, &timings), [0, 0, 255, 0, 0, 255]);
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn more_strips_than_pins_is_rejected() {
+            const STRIP: PhysicalStrip = PhysicalStrip::builder(1).build();
+            const STRIPS: [PhysicalStrip; 3] = [STRIP; 3];
+            let mut bytes = [0; 9];
+            let mut colors = [c::C_OFF; 3];
+            let strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            assert_eq!(strip.strip_count(), 3);
+
+            let clock = MockClock::new();
+            let (mut pin_0, mut pin_1) =
+                (MockPinControl::new(0, &clock), MockPinControl::new(1, &clock));
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            let hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            assert!(matches!(
+                strip.check_pins(&hc),
+                Err(StripConfigError::NotEnoughPins { needed: 3, pin_count: 2 })
+            ));
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn too_few_pins_for_the_strips_is_an_error() {
+            const STRIPS: [PhysicalStrip; 3] = [
+                PhysicalStrip::builder(1).build(),
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(0).build(),
+            ];
+            let clock = MockClock::new();
+            let mut pin_0 = MockPinControl::new(0, &clock);
+            let mut pins: [DynamicPin; 1] = [&mut pin_0];
+            let result = HardwareController::try_new(&mut pins, MockTimer::new(&clock), &STRIPS);
+            assert!(matches!(
+                result,
+                Err(StripConfigError::NotEnoughPins { needed: 2, pin_count: 1 })
+            ));
+
+            // the zero length strip at the end doesn't need a pin:
+            let (mut pin_0, mut pin_1) =
+                (MockPinControl::new(0, &clock), MockPinControl::new(1, &clock));
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            assert!(HardwareController::try_new(&mut pins, MockTimer::new(&clock), &STRIPS).is_ok());
+        }
+
+        // this is every pulse sent on a pin as its high time and the time until the next pulse:
+        #[cfg(feature = "std")]
+        fn pulses_ns(clock: &MockClock) -> std::vec::Vec<(u64, u64)> {
+            let rises: std::vec::Vec<_> = clock
+                .transitions()
+                .iter()
+                .filter(|t| t.is_high)
+                .map(|t| t.time_ns)
+                .collect();
+            let falls = clock.transitions().into_iter().filter(|t| !t.is_high);
+            rises
+                .iter()
+                .zip(falls)
+                .zip(rises.iter().skip(1))
+                .map(|((&rise, fall), &next_rise)| (fall.time_ns - rise, next_rise - rise))
+                .collect()
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn sends_a_non_standard_duty_cycle_exactly() {
+            let strip = PhysicalStrip::builder(1)
+                .timings(StripTimings { zero_h: 350, one_h: 910, full_cycle: 1390, reset_us: 50 })
+                .build();
+            let timings = &strip.strip_timings;
+            assert_eq!(timings.zero_symbol(), SymbolTimes { high_ns: 350, low_ns: 1040 });
+            assert_eq!(timings.one_symbol(), SymbolTimes { high_ns: 910, low_ns: 480 });
+
+            let clock = MockClock::new();
+            let mut pin = MockPinControl::new(0, &clock);
+            let mut pins: [DynamicPin; 1] = [&mut pin];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+
+            strip.send_bits(&mut hc, 0, [false, true, true, false]);
+            assert_eq!(pulses_ns(&clock), [(350, 1390), (910, 1390), (910, 1390)]);
+            assert_eq!(clock.transitions()[0].time_ns, 50_040);
+        }
+
+        // every preset, with the zero and one high times its datasheet allows:
+        const PRESETS: [(StripTimings, RangeInclusive<u32>, RangeInclusive<u32>); 4] = [
+            (StripTimings::WS2811_ADAFRUIT, 350..=650, 1050..=1350),
+            (StripTimings::WS2812_ADAFRUIT, 200..=500, 550..=850),
+            (StripTimings::WS2815, 220..=380, 580..=1000),
+            (StripTimings::SK6812, 150..=450, 450..=750),
+        ];
+
+        #[test]
+        fn presets_send_different_zero_and_one_bits() {
+            for (timings, _, _) in &PRESETS {
+                assert_ne!(timings.zero_symbol().high_ns, timings.one_symbol().high_ns);
+            }
+        }
+
+        #[test]
+        fn presets_match_their_datasheets() {
+            for (timings, zero_range, one_range) in &PRESETS {
+                assert!(zero_range.contains(&timings.zero_symbol().high_ns));
+                assert!(one_range.contains(&timings.one_symbol().high_ns));
+            }
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn presets_send_bytes_that_decode_back() {
+            for (timings, _, _) in PRESETS {
+                let strip = PhysicalStrip::builder(1).timings(timings).build();
+                let clock = MockClock::new();
+                let mut pin = MockPinControl::new(0, &clock);
+                let mut pins: [DynamicPin; 1] = [&mut pin];
+                let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+
+                let bytes = [0xA5, 0x0F, 0xFF];
+                strip.send_bits(
+                    &mut hc,
+                    0,
+                    LogicalStrip::bytes_as_bit_slice(&bytes).iter().by_val(),
+                );
+                assert_eq!(clock.decode_bytes(0, &strip.strip_timings), bytes);
+            }
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn pure_white_only_lights_the_white_byte() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1)
+                .color_order(ColorOrder::GRBW)
+                .build()];
+            let mut bytes = [0; 4];
+            let mut colors = [c::C_PURE_WHITE];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let mut pin = MockPinControl::new(0, &clock);
+            let mut pins: [DynamicPin; 1] = [&mut pin];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+
+            strip.send_all_sequential(&mut hc);
+            let timings = StripTimings::WS2812_ADAFRUIT;
+            assert_eq!(clock.decode_bytes(0, &timings), [0, 0, 0, 255]);
+        }
+
+        #[test]
+        fn white_extraction_moves_gray_onto_the_white_byte() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1)
+                .color_order(ColorOrder::RGBW)
+                .build()];
+            let mut bytes = [0; 4];
+            let mut colors = [c::Color::new(200, 200, 200)];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            strip.set_gamma_correction(false);
+
+            assert_eq!(strip.strip_bytes(0).unwrap(), [200, 200, 200, 0]);
+            strip.set_white_extraction(true);
+            assert_eq!(strip.strip_bytes(0).unwrap(), [0, 0, 0, 200]);
+        }
+
+        #[test]
+        fn unchanged_colors_are_only_encoded_once() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(3).build()];
+            let mut bytes = [0; 9];
+            let mut colors = [c::C_OFF; 3];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            strip.render_only();
+            strip.render_only();
+            assert_eq!(strip.encode_count, 1);
+
+            strip.set_color_at_index(1, c::C_RED);
+            strip.render_only();
+            strip.render_only();
+            assert_eq!(strip.encode_count, 2);
+
+            // setters that fail don't change anything, so they don't need a new encode either:
+            assert!(strip.try_set_color_at_index(3, c::C_RED).is_err());
+            assert!(strip.set_range_to_color(2, 4, c::C_RED).is_err());
+            assert!(strip.zone_colors_mut(Zone::new(1, 3)).is_err());
+            strip.render_only();
+            assert_eq!(strip.encode_count, 2);
+        }
+
+        #[test]
+        fn swapping_leaves_the_back_buffer_alone() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1)
+                .color_order(ColorOrder::RGB)
+                .build()];
+            let mut bytes = [0; 3];
+            let (mut front, mut back) = ([c::C_OFF], [c::C_OFF]);
+            let mut strip =
+                LogicalStrip::new_double_buffered(&mut bytes, &mut front, &mut back, &STRIPS);
+            strip.set_gamma_correction(false);
+
+            strip.set_color_at_index(0, c::C_RED);
+            strip.swap_buffers();
+            assert_eq!(strip.strip_bytes(0).unwrap(), [255, 0, 0]);
+            assert_eq!(strip.get_color_at_index(0), c::C_OFF);
+
+            strip.set_color_at_index(0, c::C_BLUE);
+            assert_eq!(strip.strip_bytes(0).unwrap(), [255, 0, 0]);
+            strip.swap_buffers();
+            assert_eq!(strip.get_color_at_index(0), c::C_RED);
+            assert_eq!(strip.strip_bytes(0).unwrap(), [0, 0, 255]);
+        }
+
+        #[test]
+        fn swapping_a_single_buffered_strip_keeps_the_bytes() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1).build()];
+            let mut bytes = [0; 3];
+            let mut colors = [c::C_RED];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            assert!(!strip.is_double_buffered());
+
+            strip.render_only();
+            strip.swap_buffers();
+            strip.render_only();
+            assert_eq!(strip.encode_count, 1);
+            assert_eq!(strip.get_color_at_index(0), c::C_RED);
+        }
+
+        #[test]
+        fn power_limit_keeps_full_white_under_budget() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(10).build()];
+            let mut bytes = [0; 30];
+            let mut colors = [c::C_WHITE; 10];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            assert_eq!(strip.estimate_current_ma(20), 600);
+
+            strip.set_power_limit_ma(Some(300), 20);
+            let byte_total: u32 = strip
+                .strip_bytes(0)
+                .unwrap()
+                .iter()
+                .map(|&b| b as u32)
+                .sum();
+            let sent_ma = byte_total * 20 / 255;
+            assert!(sent_ma <= 300, "{}mA is over the limit", sent_ma);
+            assert!(sent_ma > 250, "{}mA is dimmed much more than needed", sent_ma);
+        }
+
+        #[test]
+        fn current_estimate_includes_gamma_correction() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1)
+                .color_order(ColorOrder::RGB)
+                .build()];
+            let mut bytes = [0; 3];
+            let mut colors = [c::Color::new(128, 128, 128)];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            strip.set_gamma_correction(false);
+            let linear_ma = strip.estimate_current_ma(20);
+            strip.set_gamma_correction(true);
+            let corrected_ma = strip.estimate_current_ma(20);
+            assert_eq!(corrected_ma, 3 * 37 * 20 / 255);
+            assert!(corrected_ma < linear_ma);
+        }
+
+        #[test]
+        fn short_resets_are_reported() {
+            // 300us at 160MHz is 48000 cycles:
+            let timings = StripTimings::WS2812_ADAFRUIT;
+            assert!(!timings.check_reset_cycles(47_999, 160_000_000));
+            assert!(timings.check_reset_cycles(48_000, 160_000_000));
+            assert!(!timings.check_reset_cycles(0, 160_000_000));
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn strips_round_trip_through_postcard() {
+            let strip = PhysicalStrip::builder(5)
+                .color_order(ColorOrder::GRBW)
+                .reversed(true)
+                .build();
+            let mut buffer = [0; 64];
+            let bytes = postcard::to_slice(&strip, &mut buffer).unwrap();
+            let decoded: PhysicalStrip = postcard::from_bytes(bytes).unwrap();
+            assert_eq!((decoded.led_count, decoded.reversed), (5, true));
+            assert!(matches!(decoded.color_order, ColorOrder::GRBW));
+        }
+
+        #[test]
+        #[should_panic]
+        fn double_buffers_must_be_the_same_length() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(2).build()];
+            let mut bytes = [0; 6];
+            let (mut front, mut back) = ([c::C_OFF; 3], [c::C_OFF; 2]);
+            LogicalStrip::new_double_buffered(&mut bytes, &mut front, &mut back, &STRIPS);
+        }
+
+        #[test]
+        fn try_set_past_the_end_is_an_error() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(3).build()];
+            const NUM_LEDS: usize = crate::get_total_num_leds(&STRIPS);
+            let mut bytes = [0; 9];
+            let mut colors = [c::C_OFF; NUM_LEDS];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            assert!(strip.try_set_color_at_index(NUM_LEDS - 1, c::C_RED).is_ok());
+            let error = strip
+                .try_set_color_at_index(NUM_LEDS, c::C_RED)
+                .unwrap_err();
+            assert_eq!((error.index, error.length), (NUM_LEDS, NUM_LEDS));
+            assert_eq!(strip.get_color_at_index(NUM_LEDS - 1), c::C_RED);
+        }
+
+        #[test]
+        fn try_new_rejects_buffers_that_dont_match_the_strips() {
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(1).build(),
+            ];
+            let mut bytes = [0; 9];
+            let mut colors = [c::C_OFF; 4];
+            assert!(matches!(
+                LogicalStrip::try_new(&mut bytes, &mut colors, &STRIPS),
+                Err(StripConfigError::LedCountMismatch { led_count: 3, length: 4 })
+            ));
+
+            let mut short_bytes = [0; 8];
+            let mut colors = [c::C_OFF; 3];
+            assert!(matches!(
+                LogicalStrip::try_new(&mut short_bytes, &mut colors, &STRIPS),
+                Err(StripConfigError::BufferTooSmall { needed: 9, length: 8 })
+            ));
+            assert!(LogicalStrip::try_new(&mut bytes, &mut colors, &STRIPS).is_ok());
+        }
+
+        #[test]
+        fn filling_a_range_only_lights_that_range() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(8).build()];
+            let mut bytes = [0; 24];
+            let mut colors = [c::C_OFF; 8];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            assert!(strip.set_range_to_color(2, 5, c::C_RED).is_ok());
+            let is_lit: [bool; 8] =
+                core::array::from_fn(|index| !strip.get_color_at_index(index).is_off());
+            assert_eq!(is_lit, [false, false, true, true, true, false, false, false]);
+
+            let error = strip.set_range_to_color(6, 9, c::C_BLUE).unwrap_err();
+            assert_eq!((error.index, error.length), (9, 8));
+            assert!(strip.get_color_at_index(7).is_off());
+        }
+
+        #[test]
+        fn brightness_scales_every_channel_when_sent() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1)
+                .color_order(ColorOrder::RGB)
+                .build()];
+            let mut bytes = [0; 3];
+            let mut colors = [c::Color::new(200, 100, 50)];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            strip.set_gamma_correction(false);
+
+            strip.set_brightness(128);
+            let halved = strip.strip_bytes(0).unwrap();
+            for (&byte, full) in halved.iter().zip([200_u8, 100, 50]) {
+                assert!(byte.abs_diff(full / 2) <= 1, "{} is not half of {}", byte, full);
+            }
+
+            strip.set_brightness(255);
+            assert_eq!(strip.strip_bytes(0).unwrap(), [200, 100, 50]);
+            strip.set_brightness(0);
+            assert_eq!(strip.strip_bytes(0).unwrap(), [0, 0, 0]);
+            assert_eq!(strip.get_color_at_index(0), c::Color::new(200, 100, 50));
+        }
+
+        #[test]
+        fn two_stop_gradient_blends_through_the_middle() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(3).build()];
+            let mut bytes = [0; 9];
+            let mut colors = [c::C_OFF; 3];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            strip.set_gradient(&[c::C_RED, c::C_BLUE]);
+            assert_eq!(strip.get_color_at_index(0), c::C_RED);
+            let middle = strip.get_color_at_index(1);
+            assert_eq!(middle.g, 0);
+            assert!(middle.r.abs_diff(127) <= 1 && middle.b.abs_diff(127) <= 1);
+            assert_eq!(strip.get_color_at_index(2), c::C_BLUE);
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn send_all_async_lets_other_tasks_run_and_sends_the_same_bits() {
+            use core::{
+                future::Future,
+                task::{RawWaker, RawWakerVTable, Waker},
+            };
+
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(3).build(),
+            ];
+            let mut bytes = [0; 15];
+            let mut colors = [c::C_RED, c::C_GREEN, c::C_BLUE, c::C_WHITE, c::C_YELLOW];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let (mut pin_0, mut pin_1) =
+                (MockPinControl::new(0, &clock), MockPinControl::new(1, &clock));
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            let timings = StripTimings::WS2812_ADAFRUIT;
+
+            strip.send_all_sequential(&mut hc);
+            let blocking = [
+                clock.decode_bytes(0, &timings),
+                clock.decode_bytes(1, &timings),
+            ];
+
+            fn noop_raw_waker() -> RawWaker {
+                fn clone(_: *const ()) -> RawWaker {
+                    noop_raw_waker()
+                }
+                fn noop(_: *const ()) {}
+                static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+                RawWaker::new(core::ptr::null(), &VTABLE)
+            }
+            let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+            let mut cx = Context::from_waker(&waker);
+
+            // every time the send yields, the other "task" gets to count once:
+            clock.clear();
+            let mut other_task_runs = 0;
+            {
+                let mut send = core::pin::pin!(strip.send_all_async(&mut hc, 24));
+                while send.as_mut().poll(&mut cx).is_pending() {
+                    other_task_runs += 1;
+                }
+            }
+            let sent = [
+                clock.decode_bytes(0, &timings),
+                clock.decode_bytes(1, &timings),
+            ];
+
+            // one yield after each led except the last
+            assert_eq!(other_task_runs, 4);
+            assert_eq!(sent, blocking);
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn send_step_sends_the_same_bits_as_send_all_sequential() {
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(3).build(),
+            ];
+            let mut bytes = [0; 15];
+            let mut colors = [c::C_RED, c::C_GREEN, c::C_BLUE, c::C_WHITE, c::C_YELLOW];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let (mut pin_0, mut pin_1) =
+                (MockPinControl::new(0, &clock), MockPinControl::new(1, &clock));
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            let timings = StripTimings::WS2812_ADAFRUIT;
+
+            strip.send_all_sequential(&mut hc);
+            let blocking = [
+                clock.decode_bytes(0, &timings),
+                clock.decode_bytes(1, &timings),
+            ];
+
+            clock.clear();
+            let mut state = SendState::new();
+            let mut step_count = 0;
+            while strip.send_step(&mut hc, &mut state, 7) == SendProgress::InProgress {
+                step_count += 1;
+            }
+            let stepped = [
+                clock.decode_bytes(0, &timings),
+                clock.decode_bytes(1, &timings),
+            ];
+
+            assert!(step_count > 1);
+            assert_eq!(blocking[0].len(), 6);
+            assert_eq!(blocking[1].len(), 9);
+            assert_eq!(stepped, blocking);
+            assert!(state.is_at_frame_start());
+        }
+
+        #[test]
+        fn shift_moves_colors_along_the_strip() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(3).build()];
+            let mut bytes = [0; 9];
+            let mut colors = [c::C_RED, c::C_GREEN, c::C_BLUE];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            let colors = |strip: &LogicalStrip| -> [c::Color; 3] {
+                core::array::from_fn(|index| strip.get_color_at_index(index))
+            };
+
+            strip.shift(1, true);
+            assert_eq!(colors(&strip), [c::C_BLUE, c::C_RED, c::C_GREEN]);
+            strip.shift(-1, true);
+            assert_eq!(colors(&strip), [c::C_RED, c::C_GREEN, c::C_BLUE]);
+            strip.shift(4, true);
+            assert_eq!(colors(&strip), [c::C_BLUE, c::C_RED, c::C_GREEN]);
+            strip.shift(1, false);
+            assert_eq!(colors(&strip), [c::C_OFF, c::C_BLUE, c::C_RED]);
+        }
+
+        #[test]
+        fn blending_layers_over_the_current_colors() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(3).build()];
+            let mut bytes = [0; 9];
+            let mut colors = [c::Color::new(200, 0, 0); 3];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            strip.blend_from(&[c::Color::new(100, 50, 0), c::C_BLUE], c::BlendMode::Add);
+            assert_eq!(strip.get_color_at_index(0), c::Color::new(255, 50, 0));
+            assert_eq!(strip.get_color_at_index(1), c::Color::new(200, 0, 255));
+            assert_eq!(strip.get_color_at_index(2), c::Color::new(200, 0, 0));
+
+            strip.blend_from(&[c::Color::new(100, 90, 0)], c::BlendMode::Max);
+            assert_eq!(strip.get_color_at_index(0), c::Color::new(255, 90, 0));
+        }
+
+        #[test]
+        fn dimming_through_colors_mut_changes_every_led() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(3).build()];
+            let mut bytes = [0; 9];
+            let mut colors = [c::C_WHITE, c::Color::new(100, 50, 10), c::C_OFF];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            strip.colors_mut().for_each(|color| *color = color.dim(20));
+
+            let dimmed = [
+                c::Color::new(235, 235, 235),
+                c::Color::new(80, 30, 0),
+                c::C_OFF,
+            ];
+            assert!(strip.colors().eq(dimmed.iter()));
+        }
+
+        #[test]
+        fn filling_a_physical_strip_leaves_the_others_alone() {
+            const STRIPS: [PhysicalStrip; 3] = [
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(3).build(),
+                PhysicalStrip::builder(1).build(),
+            ];
+            let mut bytes = [0; 18];
+            let mut colors = [c::C_OFF; 6];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            strip.set_physical_strip_to_color(1, c::C_RED).unwrap();
+            let colors: [c::Color; 6] = core::array::from_fn(|i| strip.get_color_at_index(i));
+            assert_eq!(colors, [c::C_OFF, c::C_OFF, c::C_RED, c::C_RED, c::C_RED, c::C_OFF]);
+
+            assert!(matches!(
+                strip.set_physical_strip_to_color(3, c::C_RED),
+                Err(StripConfigError::NoSuchStrip { index: 3, strip_count: 3 })
+            ));
+        }
+
+        #[test]
+        fn each_strip_is_scaled_by_its_own_brightness() {
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(1).brightness(255).build(),
+                PhysicalStrip::builder(1).brightness(128).build(),
+            ];
+            let mut bytes = [0; 6];
+            let mut colors = [c::C_WHITE; 2];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            strip.set_gamma_correction(false);
+
+            assert_eq!(strip.strip_bytes(0).unwrap(), [255; 3]);
+            assert_eq!(strip.strip_bytes(1).unwrap(), [128; 3]);
+
+            // the global brightness is multiplied with each strip's own:
+            strip.set_brightness(128);
+            assert_eq!(strip.strip_bytes(0).unwrap(), [128; 3]);
+            assert_eq!(strip.strip_bytes(1).unwrap(), [64; 3]);
+        }
+
+        #[test]
+        fn color_correction_warms_up_white() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1).build()];
+            let mut bytes = [0; 3];
+            let mut colors = [c::C_WHITE];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            // no correction by default, and the bytes are sent green first:
+            assert_eq!(strip.strip_bytes(0).unwrap(), [255, 255, 255]);
+            strip.set_color_correction(c::Color::new(255, 176, 240));
+            assert_eq!(strip.strip_bytes(0).unwrap(), [176, 255, 240]);
+
+            strip.set_brightness(128);
+            assert_eq!(strip.strip_bytes(0).unwrap(), [88, 128, 120]);
+        }
+
+        #[test]
+        fn clearing_turns_every_led_off() {
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(3).build(),
+            ];
+            let mut bytes = [0; 15];
+            let mut colors = [c::C_WHITE; 5];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            strip.clear();
+            assert!(strip.colors().all(|color| color.is_off()));
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn clear_and_send_sends_every_led_off() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(3).build()];
+            let mut bytes = [0; 9];
+            let mut colors = [c::C_WHITE; 3];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let mut pin = MockPinControl::new(0, &clock);
+            let mut pins: [DynamicPin; 1] = [&mut pin];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+
+            strip.clear_and_send(&mut hc);
+            assert_eq!(clock.decode_bytes(0, &STRIPS[0].strip_timings), [0; 9]);
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn render_only_makes_the_sent_bytes_without_touching_the_pins() {
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(1).reversed(true).build(),
+            ];
+            let mut bytes = [0; 9];
+            let mut colors = [c::C_RED, c::C_BLUE, c::Color::new(1, 2, 3)];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let (mut pin_0, mut pin_1) =
+                (MockPinControl::new(0, &clock), MockPinControl::new(1, &clock));
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            let timings = StripTimings::WS2812_ADAFRUIT;
+
+            clock.clear();
+            let one_bit_count = strip.render_only();
+            let rendered = strip.bytes().to_vec();
+            assert!(clock.transitions().is_empty());
+            let ones: u32 = rendered.iter().map(|byte| byte.count_ones()).sum();
+            assert_eq!(one_bit_count, ones as usize);
+
+            strip.send_all_sequential(&mut hc);
+            let mut sent = clock.decode_bytes(0, &timings);
+            sent.extend(clock.decode_bytes(1, &timings));
+            assert_eq!(sent, rendered);
+        }
+
+        #[test]
+        fn scaled_sets_store_the_scaled_color() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(2).build()];
+            let mut bytes = [0; 6];
+            let mut colors = [c::C_OFF; 2];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let color = c::Color::new(200, 100, 50);
+            strip.set_color_at_index_scaled(0, color, 128);
+            strip.set_color_at_index_scaled(1, color, 255);
+            assert_eq!(strip.get_color_at_index(0), color.scale(128));
+            assert_eq!(strip.get_color_at_index(0), c::Color::new(100, 50, 25));
+            assert_eq!(strip.get_color_at_index(1), color);
+        }
+
+        #[test]
+        fn correction_order_changes_dimmed_gray() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1).build()];
+            let mut bytes = [0; 3];
+            let mut colors = [c::Color::new(128, 128, 128)];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            strip.set_brightness(64);
+
+            let gamma_first = strip.strip_bytes(0).unwrap()[0];
+            assert_eq!(gamma_first, c::scale8(c::GAMMA8[128], 64));
+
+            strip.set_correction_order(CorrectionOrder::BrightnessThenGamma);
+            let brightness_first = strip.strip_bytes(0).unwrap()[0];
+            assert_eq!(brightness_first, c::GAMMA8[c::scale8(128, 64) as usize]);
+
+            assert_ne!(gamma_first, brightness_first);
+        }
+
+        #[test]
+        fn filling_a_zone_only_changes_its_leds() {
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(4).build(),
+                PhysicalStrip::builder(2).build(),
+            ];
+            let mut bytes = [0; 18];
+            let mut colors = [c::C_OFF; 6];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            let colors = |strip: &LogicalStrip| -> [c::Color; 6] {
+                core::array::from_fn(|index| strip.get_color_at_index(index))
+            };
+
+            // a sub-zone in the middle of the first strip:
+            let window_top = Zone::new(1, 2);
+            strip.fill_zone(window_top, c::C_RED).unwrap();
+            assert_eq!(
+                colors(&strip),
+                [c::C_OFF, c::C_RED, c::C_RED, c::C_OFF, c::C_OFF, c::C_OFF]
+            );
+
+            let door = strip.physical_strip_zone(1).unwrap();
+            assert_eq!(door, Zone::new(4, 2));
+            strip.zone_colors_mut(door).unwrap().fill(c::C_BLUE);
+            assert_eq!(
+                colors(&strip),
+                [c::C_OFF, c::C_RED, c::C_RED, c::C_OFF, c::C_BLUE, c::C_BLUE]
+            );
+
+            assert!(strip.fill_zone(Zone::new(5, 2), c::C_GREEN).is_err());
+            assert!(strip.zone_colors_mut(Zone::new(5, 2)).is_err());
+            assert_eq!(strip.get_color_at_index(5), c::C_BLUE);
+        }
+
+        #[test]
+        #[should_panic(expected = "byte buffer holds 90 bytes but the strips need 300")]
+        fn a_strip_longer_than_its_buffer_panics_straight_away() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(100).build()];
+            let mut bytes = [0; 90];
+            let mut colors = [c::C_OFF; 100];
+            LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+        }
+
+        #[test]
+        fn mirroring_lights_both_ends() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(5).build()];
+            let mut bytes = [0; 15];
+            let mut colors = [c::C_OFF; 5];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            let colors = |strip: &LogicalStrip| -> [c::Color; 5] {
+                core::array::from_fn(|index| strip.get_color_at_index(index))
+            };
+
+            strip.set_mirrored(0, c::C_RED);
+            assert_eq!(colors(&strip), [c::C_RED, c::C_OFF, c::C_OFF, c::C_OFF, c::C_RED]);
+
+            // the center led of an odd length strip is its own mirror:
+            strip.set_mirrored(2, c::C_BLUE);
+            assert_eq!(colors(&strip), [c::C_RED, c::C_OFF, c::C_BLUE, c::C_OFF, c::C_RED]);
+        }
+
+        #[test]
+        fn compositing_half_white_over_black_is_mid_gray() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(3).build()];
+            let mut bytes = [0; 9];
+            let mut colors = [c::C_OFF; 3];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            strip.composite(&[c::C_WHITE, c::C_WHITE, c::C_WHITE], &[128, 255]);
+            assert_eq!(strip.get_color_at_index(0), c::Color::new(128, 128, 128));
+            assert_eq!(strip.get_color_at_index(1), c::C_WHITE);
+            // there is no alpha for the last led, so it is left alone:
+            assert_eq!(strip.get_color_at_index(2), c::C_OFF);
+        }
+
+        #[test]
+        fn strip_bytes_match_hand_encoded_grb() {
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(1).build(),
+                PhysicalStrip::builder(2).reversed(true).build(),
+            ];
+            let mut bytes = [0; 9];
+            let mut colors = [
+                c::Color::new(1, 2, 3),
+                c::Color::new(10, 20, 30),
+                c::Color::new(40, 50, 60),
+            ];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            strip.set_gamma_correction(false);
+
+            assert_eq!(strip.strip_bytes(0).unwrap(), [2, 1, 3]);
+            // the second strip is reversed, so its last led is sent first:
+            assert_eq!(strip.strip_bytes(1).unwrap(), [50, 40, 60, 20, 10, 30]);
+            assert!(matches!(
+                strip.strip_bytes(2),
+                Err(StripConfigError::NoSuchStrip { index: 2, strip_count: 2 })
+            ));
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn strips_can_be_reconfigured_and_sent_again() {
+            let mut strips: arrayvec::ArrayVec<PhysicalStrip, 3> = arrayvec::ArrayVec::new();
+            strips.push(PhysicalStrip::builder(2).build());
+            let mut bytes = [0; 18];
+            let mut colors = [c::C_RED; 6];
+
+            let clock = MockClock::new();
+            let (mut pin_0, mut pin_1) =
+                (MockPinControl::new(0, &clock), MockPinControl::new(1, &clock));
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            let timings = StripTimings::WS2812_ADAFRUIT;
+
+            let mut strip =
+                LogicalStrip::try_new_with_capacity(&mut bytes, &mut colors, &strips).unwrap();
+            assert_eq!(strip.led_count(), 2);
+            strip.send_all_sequential(&mut hc);
+            assert_eq!(clock.decode_bytes(0, &timings).len(), 6);
+            assert!(clock.decode_bytes(1, &timings).is_empty());
+
+            // a second strip is added at runtime, e.g. from a serial command:
+            strips.push(PhysicalStrip::builder(3).build());
+            clock.clear();
+            let mut strip =
+                LogicalStrip::try_new_with_capacity(&mut bytes, &mut colors, &strips).unwrap();
+            assert_eq!(strip.led_count(), 5);
+            strip.check_pins(&hc).unwrap();
+            strip.send_all_sequential(&mut hc);
+            assert_eq!(clock.decode_bytes(0, &timings), [0, 255, 0, 0, 255, 0]);
+            assert_eq!(clock.decode_bytes(1, &timings).len(), 9);
+
+            // more leds than the buffers have room for are rejected:
+            strips.push(PhysicalStrip::builder(2).build());
+            assert!(LogicalStrip::try_new_with_capacity(&mut bytes, &mut colors, &strips).is_err());
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn inverted_strips_send_the_opposite_waveform() {
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(1).build(),
+                PhysicalStrip::builder(1).inverted(true).build(),
+            ];
+            let mut bytes = [0; 6];
+            let mut colors = [c::Color::new(0x0F, 0xA5, 0x3C); 2];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let (mut pin_0, mut pin_1) =
+                (MockPinControl::new(0, &clock), MockPinControl::new(1, &clock));
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            strip.send_all_sequential(&mut hc);
+
+            // each level of a pin and how long it was held for:
+            let levels = |pin: usize| -> std::vec::Vec<(bool, u64)> {
+                let transitions = clock.transitions();
+                let transitions: std::vec::Vec<_> =
+                    transitions.iter().filter(|t| t.pin == pin).collect();
+                transitions
+                    .windows(2)
+                    .map(|pair| (pair[0].is_high, pair[1].time_ns - pair[0].time_ns))
+                    .collect()
+            };
+            let normal = levels(0);
+            let inverted = levels(1);
+
+            // the inverted pin is first driven high to hold the data line low for the reset:
+            let first_transition = clock.transitions().into_iter().find(|t| t.pin == 1);
+            assert!(first_transition.unwrap().is_high);
+            assert_eq!(normal.len(), 47);
+            let inverted_back: std::vec::Vec<_> = inverted[1..]
+                .iter()
+                .map(|&(is_high, time_ns)| (!is_high, time_ns))
+                .collect();
+            assert_eq!(inverted_back, normal);
+        }
+
+        #[test]
+        fn dithering_averages_out_to_the_exact_dimmed_value() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1).build()];
+            let mut bytes = [0; 3];
+            let mut colors = [c::Color::new(3, 0, 0)];
+            let mut dither_buffer = [[0; 4]; 1];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            strip.set_gamma_correction(false);
+            // 3 at half brightness is 1.51, which rounds down to 1 without dithering:
+            strip.set_brightness(128);
+            assert_eq!(strip.strip_bytes(0).unwrap()[1], 1);
+
+            strip.set_dither_buffer(Some(&mut dither_buffer));
+            let frame_count = 256;
+            let total: u32 = (0..frame_count)
+                .map(|_| strip.strip_bytes(0).unwrap()[1] as u32)
+                .sum();
+            assert_eq!(total, 3 * 129);
+        }
+
+        #[test]
+        fn stepping_toward_a_target_reaches_it_without_overshooting() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(2).build()];
+            let mut bytes = [0; 6];
+            let mut colors = [c::C_OFF, c::C_WHITE];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+            let target = [c::C_WHITE, c::C_OFF];
+
+            let mut step_count = 0;
+            while strip.colors().ne(target.iter()) {
+                strip.step_toward_target(&target, 50);
+                step_count += 1;
+            }
+            // 50, 100, 150, 200, 250 and then the last 5:
+            assert_eq!(step_count, 6);
+
+            strip.step_toward_target(&target, 50);
+            assert!(strip.colors().eq(target.iter()));
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn aborting_after_the_first_strip_skips_the_rest() {
+            use core::convert::Infallible;
+            use embedded_hal::digital::blocking::OutputPin;
+
+            // this sets the abort flag as soon as the first strip starts sending, the way an
+            // interrupt would when new colors arrive:
+            struct AbortingPin<'p> {
+                pin: MockPinControl<'p>,
+                abort: &'p AtomicBool,
+            }
+
+            impl<'p> OutputPin for AbortingPin<'p> {
+                type Error = Infallible;
+
+                fn set_low(&mut self) -> Result<(), Self::Error> {
+                    self.pin.set_low()
+                }
+
+                fn set_high(&mut self) -> Result<(), Self::Error> {
+                    self.abort.store(true, Ordering::Relaxed);
+                    self.pin.set_high()
+                }
+            }
+
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(2).build(),
+            ];
+            let mut bytes = [0; 12];
+            let mut colors = [c::C_WHITE; 4];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let abort = AtomicBool::new(false);
+            let mut pin_0 = AbortingPin { pin: MockPinControl::new(0, &clock), abort: &abort };
+            let mut pin_1 = MockPinControl::new(1, &clock);
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            let timings = StripTimings::WS2812_ADAFRUIT;
+
+            assert_eq!(strip.send_all_abortable(&mut hc, &abort), SendProgress::Aborted);
+            // the first strip is always finished, so its pin is left low:
+            assert_eq!(clock.decode_bytes(0, &timings), [255; 6]);
+            assert!(!clock.transitions().last().unwrap().is_high);
+            assert!(clock.transitions().iter().all(|t| t.pin == 0));
+
+            abort.store(false, Ordering::Relaxed);
+            clock.clear();
+            let mut pin_0 = MockPinControl::new(0, &clock);
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            assert_eq!(strip.send_all_abortable(&mut hc, &abort), SendProgress::Complete);
+            assert_eq!(clock.decode_bytes(1, &timings), [255; 6]);
+        }
+
+        #[test]
+        fn desaturating_turns_red_gray() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(2).build()];
+            let mut bytes = [0; 6];
+            let mut colors = [c::Color::new(200, 0, 0), c::Color::new(0, 0, 128)];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            strip.adjust_hsv(0, 0, 255);
+            assert_eq!(strip.get_color_at_index(0), c::Color::new(200, 200, 200));
+            assert_eq!(strip.get_color_at_index(1), c::Color::new(128, 128, 128));
+
+            // the value is scaled too, and the hue shift has nothing to turn on a gray:
+            strip.adjust_hsv(120, 255, 128);
+            assert_eq!(strip.get_color_at_index(0), c::Color::new(100, 100, 100));
+        }
+
+        #[test]
+        fn wrapping_sets_land_back_on_the_strip() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(4).build()];
+            const NUM_LEDS: usize = crate::get_total_num_leds(&STRIPS);
+            let mut bytes = [0; 12];
+            let mut colors = [c::C_OFF; NUM_LEDS];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            strip.set_color_at_index_wrapping(NUM_LEDS + 1, c::C_RED);
+            assert_eq!(strip.get_color_at_index(1), c::C_RED);
+
+            // negative offsets wrap around to the end of the strip:
+            strip.set_color_at_offset_wrapping(-1, c::C_BLUE);
+            assert_eq!(strip.get_color_at_index(NUM_LEDS - 1), c::C_BLUE);
+            strip.set_color_at_offset_wrapping(-(NUM_LEDS as isize) - 2, c::C_GREEN);
+            assert_eq!(strip.get_color_at_index(2), c::C_GREEN);
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn polling_round_robin_sends_the_same_bits_as_blocking() {
+            const STRIPS: [PhysicalStrip; 2] = [
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(3).build(),
+            ];
+            let mut bytes = [0; 15];
+            let mut colors = [c::C_RED, c::C_GREEN, c::C_BLUE, c::C_WHITE, c::C_YELLOW];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let (mut pin_0, mut pin_1) =
+                (MockPinControl::new(0, &clock), MockPinControl::new(1, &clock));
+            let mut pins: [DynamicPin; 2] = [&mut pin_0, &mut pin_1];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            let timings = StripTimings::WS2812_ADAFRUIT;
+
+            strip.send_all_sequential(&mut hc);
+            let blocking = [
+                clock.decode_bytes(0, &timings),
+                clock.decode_bytes(1, &timings),
+            ];
+
+            clock.clear();
+            let mut sends = [strip.begin_send(0).unwrap(), strip.begin_send(1).unwrap()];
+            let mut is_done = [false; 2];
+            let mut poll_count = 0;
+            while !is_done.iter().all(|&done| done) {
+                for (send, done) in sends.iter_mut().zip(is_done.iter_mut()) {
+                    *done = strip.poll_send(&mut hc, send, 5);
+                }
+                poll_count += 1;
+            }
+            let polled = [
+                clock.decode_bytes(0, &timings),
+                clock.decode_bytes(1, &timings),
+            ];
+
+            // the longer strip takes 72 bits, 5 at a time:
+            assert_eq!(poll_count, 15);
+            assert_eq!(polled, blocking);
+            assert!(strip.begin_send(2).is_err());
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn reversed_sends_flip_the_led_order() {
+            const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(2).build()];
+            let mut bytes = [0; 6];
+            let mut colors = [c::C_RED, c::C_BLUE];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let mut pin = MockPinControl::new(0, &clock);
+            let mut pins: [DynamicPin; 1] = [&mut pin];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            let timings = StripTimings::WS2812_ADAFRUIT;
+
+            strip.send_all_sequential(&mut hc);
+            assert_eq!(clock.decode_bytes(0, &timings), [0, 255, 0, 0, 0, 255]);
+
+            clock.clear();
+            strip.send_all_sequential_reversed(&mut hc);
+            assert_eq!(clock.decode_bytes(0, &timings), [0, 0, 255, 0, 255, 0]);
+
+            // the override only lasts for the one send:
+            clock.clear();
+            strip.send_all_sequential(&mut hc);
+            assert_eq!(clock.decode_bytes(0, &timings), [0, 255, 0, 0, 0, 255]);
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn sending_one_strip_only_toggles_its_pin() {
+            const STRIPS: [PhysicalStrip; 3] = [
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(1).build(),
+                PhysicalStrip::builder(2).build(),
+            ];
+            let mut bytes = [0; 15];
+            let mut colors = [c::C_OFF, c::C_OFF, c::C_RED, c::C_BLUE, c::C_BLUE];
+            let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+            let clock = MockClock::new();
+            let (mut pin_0, mut pin_1, mut pin_2) = (
+                MockPinControl::new(0, &clock),
+                MockPinControl::new(1, &clock),
+                MockPinControl::new(2, &clock),
+            );
+            let mut pins: [DynamicPin; 3] = [&mut pin_0, &mut pin_1, &mut pin_2];
+            let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+            let timings = StripTimings::WS2812_ADAFRUIT;
+
+            strip.send_strip(&mut hc, 1).unwrap();
+            assert!(clock.transitions().iter().all(|t| t.pin == 1));
+            assert_eq!(clock.decode_bytes(1, &timings), [0, 255, 0]);
+
+            assert!(matches!(
+                strip.send_strip(&mut hc, 3),
+                Err(StripConfigError::NoSuchStrip { index: 3, strip_count: 3 })
+            ));
+        }
+
+        #[test]
+        fn built_strips_default_to_a_ws2812() {
+            let defaults = PhysicalStrip::builder(5).build();
+            let ws2812 = StripTimings::WS2812_ADAFRUIT;
+            assert_eq!(defaults.led_count, 5);
+            assert!(!defaults.reversed && !defaults.inverted);
+            assert!(matches!(defaults.color_order, ColorOrder::GRB));
+            assert_eq!(defaults.brightness, 255);
+            assert_eq!(
+                (defaults.strip_timings.zero_h, defaults.strip_timings.one_h),
+                (ws2812.zero_h, ws2812.one_h)
+            );
+            assert_eq!(defaults.strip_timings.full_cycle, ws2812.full_cycle);
+            assert_eq!(defaults.strip_timings.reset_us, ws2812.reset_us);
+
+            let strip = PhysicalStrip::builder(5)
+                .reversed(true)
+                .color_order(ColorOrder::RGBW)
+                .timings(StripTimings::WS2811_ADAFRUIT)
+                .build();
+            assert!(strip.reversed);
+            assert_eq!(strip.byte_count(), 20);
+            let ws2811 = StripTimings::WS2811_ADAFRUIT;
+            assert_eq!(strip.strip_timings.full_cycle, ws2811.full_cycle);
+        }
+
+        #[test]
+        fn any_number_of_strips_fits_buffers_sized_from_them() {
+            const ONE_STRIP: [PhysicalStrip; 1] = [PhysicalStrip::builder(100).build()];
+            const THREE_STRIPS: [PhysicalStrip; 3] = [
+                PhysicalStrip::builder(2).build(),
+                PhysicalStrip::builder(3)
+                    .color_order(ColorOrder::GRBW)
+                    .build(),
+                PhysicalStrip::builder(0).build(),
+            ];
+
+            let mut bytes = [0; crate::get_total_num_bytes(&ONE_STRIP)];
+            let mut colors = [c::C_OFF; crate::get_total_num_leds(&ONE_STRIP)];
+            let strip = LogicalStrip::try_new(&mut bytes, &mut colors, &ONE_STRIP).unwrap();
+            assert_eq!((strip.strip_count(), strip.led_count()), (1, 100));
+
+            let mut bytes = [0; crate::get_total_num_bytes(&THREE_STRIPS)];
+            let mut colors = [c::C_OFF; crate::get_total_num_leds(&THREE_STRIPS)];
+            let mut strip = LogicalStrip::try_new(&mut bytes, &mut colors, &THREE_STRIPS).unwrap();
+            assert_eq!((strip.strip_count(), strip.led_count()), (3, 5));
+            assert_eq!(strip.strip_bytes(1).unwrap().len(), 12);
         }
     }
 }
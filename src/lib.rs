@@ -1,13 +1,25 @@
 #![no_std]
 
+// the mock hardware uses Vec, so it is only available on hosts with std:
+#[cfg(feature = "std")]
+extern crate std;
+
 pub mod animations;
 pub mod background;
 pub mod colors;
+#[cfg(feature = "debug-serial")]
+pub mod debug_serial;
 pub mod default_animations;
 pub mod foreground;
 pub mod hardware;
 pub mod leds;
 pub mod lighting_controller;
+pub mod math;
+pub mod matrix;
+#[cfg(feature = "std")]
+pub mod mock;
+#[cfg(feature = "smart-leds-trait")]
+pub mod smart_leds;
 pub mod trigger;
 pub mod utility;
 
@@ -36,3 +48,74 @@ pub const fn get_total_num_leds(strips: &[strip::PhysicalStrip]) -> usize {
     }
     total
 }
+
+pub const fn get_total_num_bytes(strips: &[strip::PhysicalStrip]) -> usize {
+    let mut index = 0;
+    let mut total = 0;
+    while index < strips.len() {
+        total += strips[index].byte_count();
+        index += 1;
+    }
+    total
+}
+
+/// This is the byte count of the longest strip, which is the most bytes any single strip needs. An
+/// SPI buffer for sending one strip at a time needs SPI_BYTES_PER_BYTE times as many bytes. Like
+/// the other size helpers it is a const fn, so buffer sizes can be worked out from a const array of
+/// strips:
+///
+/// ```
+/// use bl602_ws2811::leds::ws28xx::{ColorOrder, PhysicalStrip};
+/// use bl602_ws2811::{get_max_strip_num_bytes, get_total_num_bytes, get_total_num_leds};
+///
+/// const ALL_STRIPS: [PhysicalStrip; 2] = [
+///     PhysicalStrip::builder(10).build(),
+///     PhysicalStrip::builder(4).color_order(ColorOrder::GRBW).build(),
+/// ];
+/// const NUM_LEDS: usize = get_total_num_leds(&ALL_STRIPS);
+/// const NUM_BYTES: usize = get_total_num_bytes(&ALL_STRIPS);
+/// const MAX_STRIP_BYTES: usize = get_max_strip_num_bytes(&ALL_STRIPS);
+///
+/// assert_eq!(NUM_LEDS, 14);
+/// assert_eq!(NUM_BYTES, 10 * 3 + 4 * 4);
+/// assert_eq!(MAX_STRIP_BYTES, 30);
+/// ```
+pub const fn get_max_strip_num_bytes(strips: &[strip::PhysicalStrip]) -> usize {
+    let mut index = 0;
+    let mut max = 0;
+    while index < strips.len() {
+        if strips[index].byte_count() > max {
+            max = strips[index].byte_count();
+        }
+        index += 1;
+    }
+    max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strip::{ColorOrder, PhysicalStrip};
+
+    const STRIPS: [PhysicalStrip; 3] = [
+        PhysicalStrip::builder(5).build(),
+        PhysicalStrip::builder(0).build(),
+        PhysicalStrip::builder(8)
+            .color_order(ColorOrder::RGBW)
+            .build(),
+    ];
+
+    #[test]
+    fn size_helpers_add_up_every_strip() {
+        assert_eq!(get_total_num_leds(&STRIPS), 13);
+        assert_eq!(get_total_num_bytes(&STRIPS), 5 * 3 + 8 * 4);
+        assert_eq!(get_max_strip_num_bytes(&STRIPS), 32);
+    }
+
+    #[test]
+    fn size_helpers_handle_no_strips() {
+        assert_eq!(get_total_num_leds(&[]), 0);
+        assert_eq!(get_total_num_bytes(&[]), 0);
+        assert_eq!(get_max_strip_num_bytes(&[]), 0);
+    }
+}
@@ -36,6 +36,10 @@ where
     pub fn update(&mut self, hc: &mut HardwareController<impl PeriodicTimer>) {
         // Only update if it's been longer than the frame rate period since the last update:
         if self.timer.periodic_check_timeout().is_ok() {
+            // the cycle counter only exists on the chip, so frames aren't timed on the host:
+            #[cfg(all(feature = "defmt", target_arch = "riscv32"))]
+            let frame_start = riscv::register::mcycle::read();
+
             for animation in self.animations.iter_mut() {
                 animation.update();
 
@@ -47,7 +51,16 @@ where
                     self.logical_strip.set_color_at_index(index, color);
                 }
             }
+            if self.logical_strip.is_double_buffered() {
+                self.logical_strip.swap_buffers();
+            }
             self.logical_strip.send_all_sequential(hc);
+
+            #[cfg(all(feature = "defmt", target_arch = "riscv32"))]
+            defmt::debug!(
+                "frame took {} cycles",
+                riscv::register::mcycle::read().wrapping_sub(frame_start)
+            );
         }
     }
 
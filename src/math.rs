@@ -0,0 +1,88 @@
+// Integer trig for animations, since there is no FPU. A full turn is 256 steps for the 8 bit
+// functions and 65536 steps for the 16 bit ones, so angles wrap around for free.
+
+/// This is the first quarter of a sine wave from 0 to 90 degrees in 64 steps, scaled to 0..=127.
+const SIN_QUARTER: [u8; 65] = [
+    0, 3, 6, 9, 12, 16, 19, 22, 25, 28, 31, 34, 37, 40, 43, 46, 49, 51, 54, 57, 60, 63, 65, 68, 71,
+    73, 76, 78, 81, 83, 85, 88, 90, 92, 94, 96, 98, 100, 102, 104, 106, 107, 109, 111, 112, 113,
+    115, 116, 117, 118, 120, 121, 122, 122, 123, 124, 125, 125, 126, 126, 126, 127, 127, 127, 127,
+];
+
+// this is an integer sine for use without an FPU, where a full turn is 256 steps of theta. The
+// result is centered on 128 and goes from 1 at the bottom of the wave to 255 at the top:
+pub fn sin8(theta: u8) -> u8 {
+    let index = (theta & 0x3F) as usize;
+    let quarter = match theta & 0x40 {
+        0 => SIN_QUARTER[index],
+        _ => SIN_QUARTER[64 - index],
+    };
+    match theta & 0x80 {
+        0 => 128 + quarter,
+        _ => 128 - quarter,
+    }
+}
+
+// this is the same as sin8(), a quarter of a turn ahead:
+pub fn cos8(theta: u8) -> u8 {
+    sin8(theta.wrapping_add(64))
+}
+
+/// sin16() approximates the first quarter of a sine wave with 8 straight line sections, which
+/// keeps it within about 0.7% of the real value. This is the value at the start of each section.
+const SIN16_BASE: [u16; 8] = [0, 6393, 12539, 18204, 23170, 27245, 30273, 32137];
+
+/// This is how much each section of sin16() rises for every 2 steps of theta.
+const SIN16_SLOPE: [u8; 8] = [49, 48, 44, 38, 31, 23, 14, 4];
+
+// this is a finer integer sine where a full turn is 65536 steps of theta. The result goes from
+// -32645 at the bottom of the wave to 32645 at the top, a little short of the full i16 range:
+pub fn sin16(theta: u16) -> i16 {
+    let offset = (theta & 0x3FFF) >> 3;
+    let offset = match theta & 0x4000 {
+        0 => offset,
+        _ => 2047 - offset,
+    };
+    let section = (offset / 256) as usize;
+    let section_offset = (offset as u8 / 2) as u16;
+    let y = (SIN16_BASE[section] + SIN16_SLOPE[section] as u16 * section_offset) as i16;
+    match theta & 0x8000 {
+        0 => y,
+        _ => -y,
+    }
+}
+
+// this is the same as sin16(), a quarter of a turn ahead:
+pub fn cos16(theta: u16) -> i16 {
+    sin16(theta.wrapping_add(16384))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sin8_hits_the_quarter_turns() {
+        assert_eq!(sin8(0), 128);
+        assert_eq!(sin8(64), 255);
+        assert_eq!(sin8(128), 128);
+        assert_eq!(sin8(192), 1);
+        assert_eq!(cos8(0), 255);
+    }
+
+    #[test]
+    fn sin8_is_symmetric() {
+        for theta in 0..=255_u8 {
+            assert_eq!(sin8(theta) as u16 + sin8(theta.wrapping_add(128)) as u16, 256);
+            assert_eq!(sin8(theta), sin8(128_u8.wrapping_sub(theta)));
+        }
+    }
+
+    #[test]
+    fn sin16_hits_the_quarter_turns() {
+        assert_eq!(sin16(0), 0);
+        assert_eq!(sin16(16384), 32645);
+        assert!(sin16(32768).abs() <= 256);
+        assert_eq!(sin16(49152), -32645);
+        assert_eq!(cos16(0), sin16(16384));
+    }
+}
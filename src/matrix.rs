@@ -0,0 +1,123 @@
+use crate::colors::Color;
+use crate::leds::ws28xx::LogicalStrip;
+
+/// How the rows of a matrix are wired together. Progressive rows all run the same way, so the end
+/// of each row is wired back to the start of the next. Serpentine rows snake back and forth, so
+/// every odd row runs backwards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MatrixLayout {
+    Progressive,
+    Serpentine,
+}
+
+/// This is returned when trying to access a coordinate outside of a Matrix.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CoordinateError {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// This lets the leds of a LogicalStrip be drawn on as a 2D grid of width by height leds, with
+/// (0, 0) at the first led of the strip and y counting rows. Any leds past the end of the grid
+/// are left alone.
+pub struct Matrix<'s, 'a> {
+    strip: &'s mut LogicalStrip<'a>,
+    width: usize,
+    height: usize,
+    layout: MatrixLayout,
+}
+
+impl<'s, 'a> Matrix<'s, 'a> {
+    pub fn new(
+        strip: &'s mut LogicalStrip<'a>,
+        width: usize,
+        height: usize,
+        layout: MatrixLayout,
+    ) -> Self {
+        Matrix { strip, width, height, layout }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // this is the index in the strip of the led at x, y, or an error if it's outside of the grid
+    // or past the end of the strip:
+    pub fn index_of(&self, x: usize, y: usize) -> Result<usize, CoordinateError> {
+        let error = CoordinateError { x, y, width: self.width, height: self.height };
+        if x >= self.width || y >= self.height {
+            return Err(error);
+        }
+
+        let x = match self.layout {
+            MatrixLayout::Serpentine if y % 2 == 1 => self.width - 1 - x,
+            _ => x,
+        };
+        let index = y * self.width + x;
+        match index < self.strip.led_count() {
+            true => Ok(index),
+            false => Err(error),
+        }
+    }
+
+    pub fn set_xy(&mut self, x: usize, y: usize, color: Color) -> Result<(), CoordinateError> {
+        let index = self.index_of(x, y)?;
+        self.strip.set_color_at_index(index, color);
+        Ok(())
+    }
+
+    pub fn get_xy(&self, x: usize, y: usize) -> Result<Color, CoordinateError> {
+        let index = self.index_of(x, y)?;
+        Ok(self.strip.get_color_at_index(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::{C_OFF, C_RED};
+    use crate::leds::ws28xx::PhysicalStrip;
+
+    const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(12).build()];
+
+    #[test]
+    fn serpentine_rows_run_backwards_every_other_row() {
+        let mut bytes = [0; 36];
+        let mut colors = [C_OFF; 12];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+        let progressive = Matrix::new(&mut strip, 4, 3, MatrixLayout::Progressive);
+        assert_eq!(progressive.index_of(0, 1), Ok(4));
+        assert_eq!(progressive.index_of(1, 1), Ok(5));
+
+        let mut serpentine = Matrix::new(&mut strip, 4, 3, MatrixLayout::Serpentine);
+        assert_eq!(serpentine.index_of(0, 1), Ok(7));
+        assert_eq!(serpentine.index_of(1, 1), Ok(6));
+        assert_eq!(serpentine.index_of(1, 2), Ok(9));
+
+        serpentine.set_xy(0, 1, C_RED).unwrap();
+        assert_eq!(serpentine.get_xy(0, 1), Ok(C_RED));
+        assert_eq!(strip.get_color_at_index(7), C_RED);
+    }
+
+    #[test]
+    fn coordinates_outside_the_grid_are_errors() {
+        let mut bytes = [0; 36];
+        let mut colors = [C_OFF; 12];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+        // the grid is bigger than the strip, so the last row is only partly there:
+        let mut matrix = Matrix::new(&mut strip, 5, 3, MatrixLayout::Progressive);
+
+        let error = CoordinateError { x: 5, y: 0, width: 5, height: 3 };
+        assert_eq!(matrix.set_xy(5, 0, C_RED), Err(error));
+        assert!(matrix.get_xy(0, 3).is_err());
+        assert!(matrix.index_of(2, 2).is_err());
+        assert_eq!(matrix.index_of(1, 2), Ok(11));
+    }
+}
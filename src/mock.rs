@@ -0,0 +1,200 @@
+use crate::hardware::{PeriodicTimer, TimerError, TimerMark};
+use crate::leds::ws28xx::StripTimings;
+use core::cell::{Cell, RefCell};
+use core::convert::Infallible;
+use embedded_hal::digital::blocking::OutputPin;
+use embedded_time::duration::*;
+use std::vec::Vec;
+
+/// A single change of a mock pin's output, stamped with the time it happened at in nanoseconds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Transition {
+    pub pin: usize,
+    pub is_high: bool,
+    pub time_ns: u64,
+}
+
+/// This keeps the time for the mock pins and timer so pin transitions can be recorded with the
+/// time that passed between them. The clock only moves forward when the MockTimer waits, the
+/// same way the real sending code only moves on once the timer fires.
+#[derive(Default)]
+pub struct MockClock {
+    time_ns: Cell<u64>,
+    period_ns: Cell<u64>,
+    period_start_ns: Cell<u64>,
+    marks_ns: Cell<[u64; 2]>,
+    transitions: RefCell<Vec<Transition>>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        MockClock::default()
+    }
+
+    // this is a copy of every pin transition recorded so far, in the order they happened:
+    pub fn transitions(&self) -> Vec<Transition> {
+        self.transitions.borrow().clone()
+    }
+
+    // this forgets all the transitions recorded so far:
+    pub fn clear(&self) {
+        self.transitions.borrow_mut().clear();
+    }
+
+    // this is how many nanoseconds have passed on the clock:
+    pub fn now_ns(&self) -> u64 {
+        self.time_ns.get()
+    }
+
+    // this moves the clock forward without waiting on the timer, to stand in for time spent
+    // doing work between waits:
+    pub fn advance(&self, ns: u64) {
+        self.time_ns.set(self.time_ns.get() + ns);
+    }
+
+    // this turns the recorded transitions for a pin back into bytes, using the midpoint between
+    // the zero and one high times to decide the value of each bit. Any bits left over at the end
+    // that don't make up a whole byte are dropped:
+    pub fn decode_bytes(&self, pin: usize, timings: &StripTimings) -> Vec<u8> {
+        let threshold_ns = (timings.zero_h + timings.one_h) as u64 / 2;
+        let mut bytes = Vec::new();
+        let (mut byte, mut bit_count) = (0_u8, 0);
+        let mut high_since = None;
+
+        for transition in self.transitions.borrow().iter().filter(|t| t.pin == pin) {
+            match (transition.is_high, high_since) {
+                (true, _) => high_since = Some(transition.time_ns),
+                (false, Some(start)) => {
+                    let is_one = transition.time_ns - start > threshold_ns;
+                    byte = (byte << 1) | is_one as u8;
+                    bit_count += 1;
+                    if bit_count == 8 {
+                        bytes.push(byte);
+                        (byte, bit_count) = (0, 0);
+                    }
+                    high_since = None;
+                }
+                (false, None) => {}
+            }
+        }
+        bytes
+    }
+
+    fn record(&self, pin: usize, is_high: bool) {
+        let time_ns = self.time_ns.get();
+        self.transitions
+            .borrow_mut()
+            .push(Transition { pin, is_high, time_ns });
+    }
+}
+
+/// A stand-in for a real output pin that records every transition in a MockClock, so the bits
+/// sent by `send_bits` can be checked without a board. Only changes of the output are recorded.
+pub struct MockPinControl<'c> {
+    pin: usize,
+    is_high: bool,
+    clock: &'c MockClock,
+}
+
+impl<'c> MockPinControl<'c> {
+    pub fn new(pin: usize, clock: &'c MockClock) -> Self {
+        MockPinControl { pin, is_high: false, clock }
+    }
+}
+
+impl<'c> OutputPin for MockPinControl<'c> {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        if self.is_high {
+            self.is_high = false;
+            self.clock.record(self.pin, false);
+        }
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        if !self.is_high {
+            self.is_high = true;
+            self.clock.record(self.pin, true);
+        }
+        Ok(())
+    }
+}
+
+/// A stand-in for a real periodic timer that never blocks. Every wait moves the MockClock forward
+/// to the end of the current period or to the mark being waited for, and timeouts are always
+/// reported as expired.
+pub struct MockTimer<'c> {
+    clock: &'c MockClock,
+}
+
+impl<'c> MockTimer<'c> {
+    pub fn new(clock: &'c MockClock) -> Self {
+        MockTimer { clock }
+    }
+}
+
+impl<'c> PeriodicTimer for MockTimer<'c> {
+    fn periodic_start(&mut self, time: impl Into<Nanoseconds<u64>>) {
+        let time: Nanoseconds<u64> = time.into();
+        self.clock.period_ns.set(time.0);
+        self.clock.period_start_ns.set(self.clock.time_ns.get());
+    }
+
+    // a period that has already ended doesn't wait at all, but the next period still starts
+    // where it ended, the same way the real timer keeps counting:
+    fn periodic_wait(&mut self) {
+        let clock = self.clock;
+        let period_end_ns = clock.period_start_ns.get() + clock.period_ns.get();
+        clock.time_ns.set(clock.time_ns.get().max(period_end_ns));
+        clock.period_start_ns.set(period_end_ns);
+    }
+
+    fn periodic_check_timeout(&mut self) -> Result<(), TimerError> {
+        self.periodic_wait();
+        Ok(())
+    }
+
+    fn set_marks(
+        &mut self,
+        first: impl Into<Nanoseconds<u64>>,
+        second: impl Into<Nanoseconds<u64>>,
+    ) {
+        let (first, second): (Nanoseconds<u64>, Nanoseconds<u64>) = (first.into(), second.into());
+        self.clock.marks_ns.set([first.0, second.0]);
+    }
+
+    // a mark that has already passed in the current period doesn't wait at all:
+    fn wait_for_mark(&mut self, mark: TimerMark) {
+        let clock = self.clock;
+        let mark_ns = clock.marks_ns.get()[mark as usize];
+        let mark_time_ns = clock.period_start_ns.get() + mark_ns;
+        clock.time_ns.set(clock.time_ns.get().max(mark_time_ns));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::C_RED;
+    use crate::hardware::{DynamicPin, HardwareController};
+    use crate::leds::ws28xx::{LogicalStrip, PhysicalStrip};
+
+    #[test]
+    fn one_red_led_decodes_to_grb_bytes() {
+        const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(1).build()];
+        let mut bytes = [0; 3];
+        let mut colors = [C_RED];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+        let clock = MockClock::new();
+        let mut pin = MockPinControl::new(0, &clock);
+        let mut pins: [DynamicPin; 1] = [&mut pin];
+        let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+        strip.send_all_sequential(&mut hc);
+
+        let decoded = clock.decode_bytes(0, &StripTimings::WS2812_ADAFRUIT);
+        assert_eq!(decoded, [0, 255, 0]);
+    }
+}
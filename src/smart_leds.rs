@@ -0,0 +1,91 @@
+use crate::colors::Color;
+use crate::hardware::{HardwareController, PeriodicTimer};
+use crate::leds::ws28xx::LogicalStrip;
+use core::convert::Infallible;
+use smart_leds_trait::{SmartLedsWrite, RGB8};
+
+impl From<RGB8> for Color {
+    fn from(rgb: RGB8) -> Self {
+        Color::new(rgb.r, rgb.g, rgb.b)
+    }
+}
+
+/// This lets effects written for the smart-leds ecosystem draw onto a LogicalStrip. Each `write()`
+/// fills the strip's colors starting at the first led and then sends them out sequentially, so the
+/// color order, reversal, gamma and brightness of each strip still apply as usual.
+pub struct SmartLedsStrip<'s, 'a, 'h, T> {
+    strip: &'s mut LogicalStrip<'a>,
+    hc: &'s mut HardwareController<'h, T>,
+}
+
+impl<'s, 'a, 'h, T> SmartLedsStrip<'s, 'a, 'h, T> {
+    pub fn new(strip: &'s mut LogicalStrip<'a>, hc: &'s mut HardwareController<'h, T>) -> Self {
+        SmartLedsStrip { strip, hc }
+    }
+}
+
+impl<'s, 'a, 'h, T> SmartLedsWrite for SmartLedsStrip<'s, 'a, 'h, T>
+where
+    T: PeriodicTimer,
+{
+    type Error = Infallible;
+    type Color = RGB8;
+
+    // colors past the end of the strip are ignored, and leds past the end of the colors keep
+    // whatever color they had before:
+    fn write<I, C>(&mut self, iterator: I) -> Result<(), Self::Error>
+    where
+        I: Iterator<Item = C>,
+        C: Into<Self::Color>,
+    {
+        for (index, color) in iterator.take(self.strip.led_count()).enumerate() {
+            self.strip
+                .set_color_at_index(index, Color::from(color.into()));
+        }
+        self.strip.send_all_sequential(self.hc);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::{C_OFF, C_RED};
+    #[cfg(feature = "std")]
+    use crate::{
+        hardware::DynamicPin,
+        leds::ws28xx::PhysicalStrip,
+        mock::{MockClock, MockPinControl, MockTimer},
+    };
+
+    #[test]
+    fn rgb8_converts_to_the_same_color() {
+        assert_eq!(Color::from(RGB8 { r: 255, g: 0, b: 0 }), C_RED);
+        assert_eq!(Color::from(RGB8 { r: 1, g: 2, b: 3 }), Color::new(1, 2, 3));
+        assert_eq!(Color::from(RGB8 { r: 0, g: 0, b: 0 }), C_OFF);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn writing_rgb8_sets_the_strip_colors() {
+        const STRIPS: [PhysicalStrip; 1] = [PhysicalStrip::builder(3).build()];
+        let mut bytes = [0; 9];
+        let mut colors = [C_OFF; 3];
+        let mut strip = LogicalStrip::new(&mut bytes, &mut colors, &STRIPS);
+
+        let clock = MockClock::new();
+        let mut pin = MockPinControl::new(0, &clock);
+        let mut pins: [DynamicPin; 1] = [&mut pin];
+        let mut hc = HardwareController::new(&mut pins, MockTimer::new(&clock));
+
+        let rgb = [RGB8 { r: 10, g: 20, b: 30 }, RGB8 { r: 255, g: 0, b: 0 }];
+        SmartLedsStrip::new(&mut strip, &mut hc)
+            .write(rgb.iter().copied())
+            .unwrap();
+
+        assert_eq!(strip.get_color_at_index(0), Color::new(10, 20, 30));
+        assert_eq!(strip.get_color_at_index(1), C_RED);
+        assert_eq!(strip.get_color_at_index(2), C_OFF);
+        assert_eq!(clock.decode_bytes(0, &STRIPS[0].strip_timings).len(), 9);
+    }
+}
@@ -6,13 +6,11 @@ use crate::{
 };
 
 use bl602_hal as hal;
-use core::fmt::Write;
 use embedded_time::rate::*;
 use hal::{
     clock::{Clocks, Strict, SysclkFreq, UART_PLL_FREQ},
     gpio::*,
     pac,
-    serial::*,
     timer::*,
 };
 
@@ -40,23 +38,6 @@ pub fn init_timers(
     (timer_ch0, timer_ch1)
 }
 
-pub fn init_usb_serial<MODE>(
-    uart: pac::UART,
-    clocks: Clocks,
-    baud_rate: Baud,
-    tx_pin: Pin16<MODE>,
-    rx_pin: Pin7<MODE>,
-    tx_mux: UartMux0<Uart0Cts>,
-    rx_mux: UartMux7<Uart0Cts>,
-) -> impl Write {
-    let tx = tx_pin.into_uart_sig0();
-    let rx = rx_pin.into_uart_sig7();
-    let tx_mux = tx_mux.into_uart0_tx();
-    let rx_mux = rx_mux.into_uart0_rx();
-
-    Serial::uart0(uart, Config::default().baudrate(baud_rate), ((tx, tx_mux), (rx, rx_mux)), clocks)
-}
-
 pub fn convert_ns_to_frames(nanos: u64, frame_rate: Hertz) -> usize {
     (nanos * frame_rate.integer() as u64 / 1_000_000_000_u64) as usize
 }